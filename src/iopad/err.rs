@@ -6,6 +6,15 @@ pub enum FioPadError {
     NotReady,
     NotNotSupport,
     Timeout,
+    /// A pad register doesn't hold the value that was written
+    VerifyFailed {
+        /// Offset of the pad register
+        offset: u32,
+        /// Expected field bits
+        expected: u32,
+        /// Field bits read back
+        actual: u32,
+    },
 }
 
 impl RegError for FioPadError {
@@ -9,17 +9,30 @@
 //! - Pull-up/pull-down resistor configuration
 //! - Drive strength configuration
 //! - Input/output delay tuning (coarse and fine)
+//! - Pin profiles applying the pinmux of a whole MCI instance
+//! - Snapshots of pad settings with diff and restore
 
 #![allow(unused)]
 pub(crate) mod constants;
 mod err;
+mod pad;
+mod profile;
 pub(crate) mod regs;
+mod snapshot;
 
+use crate::mci::constants::MCIId;
 use crate::regs::{BitsOps, FlagReg, Reg};
+use alloc::vec::Vec;
 pub use constants::*;
 use core::ptr::NonNull;
-use err::*;
+pub use err::*;
+use log::*;
+use pad::FIOPAD_REG0_CONFIG_MASK;
+pub use pad::{Pad, PadId};
+pub use profile::*;
 use regs::{XReg0, XReg1};
+use snapshot::FIOPAD_REG1_DELAY_MASK;
+pub use snapshot::{IoPadSnapshot, PadDiff, PadState};
 
 type IoPadReg = Reg<FioPadError>;
 
@@ -71,6 +84,22 @@ impl IoPad {
         self.reg.addr
     }
 
+    /// Get a handle to a pad selected at runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Pad to operate on
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let pad = PadId::from_name("AJ49").unwrap();
+    /// iopad.pad(pad).drive_set(FioPadDrive::Drv4);
+    /// ```
+    pub fn pad(&mut self, id: PadId) -> Pad<'_> {
+        Pad::new(&self.reg, id)
+    }
+
     /// Get the function multiplexing setting for a pad.
     ///
     /// # Type Parameters
@@ -309,4 +338,151 @@ impl IoPad {
             });
         }
     }
+
+    /// Program function, pull and drive strength of every pad in a profile.
+    ///
+    /// Unlike [`config_set`](Self::config_set) the fields are cleared before
+    /// being written, so stale bootloader settings are overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - Pin profile of the MCI instance
+    pub fn profile_apply(&mut self, profile: &MCIPinProfile) {
+        for setting in profile.pads {
+            self.pad(setting.pad)
+                .config_set(setting.func, setting.pull, setting.drive);
+        }
+    }
+
+    /// Check that every pad in a profile holds the expected settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - Pin profile of the MCI instance
+    ///
+    /// # Errors
+    ///
+    /// Returns `FioPadError::VerifyFailed` for the first pad whose
+    /// function, pull or drive strength differs from the profile.
+    pub fn profile_verify(&self, profile: &MCIPinProfile) -> FioPadResult {
+        for setting in profile.pads {
+            let offset = setting.pad.reg0_offset();
+            let actual = self.reg.read_32(offset) & FIOPAD_REG0_CONFIG_MASK;
+            let expected = setting.reg0_bits();
+            if actual != expected {
+                error!(
+                    "{:?} pad {:?} ({}): expected 0x{:x}, read 0x{:x}",
+                    profile.instance_id,
+                    setting.signal,
+                    setting.pad.name(),
+                    expected,
+                    actual
+                );
+                return Err(FioPadError::VerifyFailed {
+                    offset,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a profile and read it back.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - Pin profile of the MCI instance
+    ///
+    /// # Errors
+    ///
+    /// See [`profile_verify`](Self::profile_verify).
+    pub fn profile_apply_verified(&mut self, profile: &MCIPinProfile) -> FioPadResult {
+        self.profile_apply(profile);
+        self.profile_verify(profile)
+    }
+
+    /// Capture the settings of the given pads.
+    ///
+    /// # Arguments
+    ///
+    /// * `pads` - Pads to capture
+    pub fn snapshot(&self, pads: &[PadId]) -> IoPadSnapshot {
+        let states = pads
+            .iter()
+            .map(|&pad| PadState {
+                pad,
+                reg0: self.reg.read_32(pad.reg0_offset()) & FIOPAD_REG0_CONFIG_MASK,
+                reg1: pad
+                    .reg1_offset()
+                    .map(|offset| self.reg.read_32(offset) & FIOPAD_REG1_DELAY_MASK),
+            })
+            .collect();
+        IoPadSnapshot::from_states(states)
+    }
+
+    /// Capture the settings of every pad the built-in profiles of MCI0 and
+    /// MCI1 use.
+    pub fn snapshot_mci(&self) -> IoPadSnapshot {
+        let pads: Vec<PadId> = [MCIId::MCI0, MCIId::MCI1]
+            .iter()
+            .flat_map(|&id| MCIPinProfile::lookup(id).pads)
+            .map(|setting| setting.pad)
+            .collect();
+        self.snapshot(&pads)
+    }
+
+    /// Write a snapshot back and read it back.
+    ///
+    /// Only the function/pull/drive and delay fields are touched. The
+    /// snapshot is checked against the pad table before the first write, so
+    /// a malformed snapshot never leaves the pads half restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - Settings to restore
+    ///
+    /// # Errors
+    ///
+    /// Returns `FioPadError::InvalParam` if the snapshot doesn't match the
+    /// pad table, or `FioPadError::VerifyFailed` if a register doesn't hold
+    /// the restored value afterwards.
+    pub fn restore(&mut self, snapshot: &IoPadSnapshot) -> FioPadResult {
+        if snapshot
+            .pads()
+            .iter()
+            .any(|state| state.reg1.is_some() != state.pad.reg1_offset().is_some())
+        {
+            return Err(FioPadError::InvalParam);
+        }
+
+        let fields = |state: &PadState| {
+            let mut fields = [(state.pad.reg0_offset(), FIOPAD_REG0_CONFIG_MASK, state.reg0); 2];
+            if let (Some(offset), Some(reg1)) = (state.pad.reg1_offset(), state.reg1) {
+                fields[1] = (offset, FIOPAD_REG1_DELAY_MASK, reg1);
+            }
+            fields
+        };
+
+        for state in snapshot.pads() {
+            for (offset, mask, bits) in fields(state) {
+                let val = self.reg.read_32(offset);
+                self.reg.write_32(offset, (val & !mask) | (bits & mask));
+            }
+        }
+
+        for state in snapshot.pads() {
+            for (offset, mask, expected) in fields(state) {
+                let actual = self.reg.read_32(offset) & mask;
+                if actual != expected & mask {
+                    return Err(FioPadError::VerifyFailed {
+                        offset,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,391 @@
+//! # Runtime Pad Table
+//!
+//! [`PadId`] names every pad of the FIOPAD block together with its register
+//! offsets, so pads can be picked from data (e.g. device tree `pinctrl`
+//! entries) instead of through one bitflags type per register.
+//! [`IoPad::pad`](super::IoPad::pad) returns a [`Pad`] handle with the same
+//! operations as the generic `IoPad` accessors.
+
+use super::IoPadReg;
+use super::constants::*;
+use super::err::*;
+
+/// Generate [`PadId`] from `name => reg0 offset[, reg1 offset];` entries.
+macro_rules! pad_table {
+    ($($name:ident => $reg0:expr $(, $reg1:expr)?;)*) => {
+        /// Pad identifier, named after the ball of the E2000 package.
+        #[allow(clippy::upper_case_acronyms)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum PadId {
+            $(
+                #[doc = concat!("Pad ", stringify!($name))]
+                $name,
+            )*
+        }
+
+        impl PadId {
+            /// All pads, ordered by register offset
+            pub const ALL: &'static [PadId] = &[$(PadId::$name,)*];
+
+            /// Pad name as written in the datasheet, e.g. `"AJ49"`
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(PadId::$name => stringify!($name),)*
+                }
+            }
+
+            /// Offset of the function/pull/drive register
+            pub const fn reg0_offset(self) -> u32 {
+                match self {
+                    $(PadId::$name => $reg0,)*
+                }
+            }
+
+            /// Offset of the delay register, `None` if the pad has no delay line
+            pub const fn reg1_offset(self) -> Option<u32> {
+                match self {
+                    $(PadId::$name => pad_table!(@reg1 $($reg1)?),)*
+                }
+            }
+        }
+    };
+    (@reg1 $reg1:expr) => { Some($reg1) };
+    (@reg1) => { None };
+}
+
+pad_table! {
+    AN59 => FIOPAD_AN59_REG0_OFFSET;
+    AW47 => FIOPAD_AW47_REG0_OFFSET;
+    AR55 => FIOPAD_AR55_REG0_OFFSET;
+    AJ55 => FIOPAD_AJ55_REG0_OFFSET, FIOPAD_AJ55_REG1_OFFSET;
+    AL55 => FIOPAD_AL55_REG0_OFFSET, FIOPAD_AL55_REG1_OFFSET;
+    AL53 => FIOPAD_AL53_REG0_OFFSET, FIOPAD_AL53_REG1_OFFSET;
+    AN51 => FIOPAD_AN51_REG0_OFFSET, FIOPAD_AN51_REG1_OFFSET;
+    AR51 => FIOPAD_AR51_REG0_OFFSET, FIOPAD_AR51_REG1_OFFSET;
+    BA57 => FIOPAD_BA57_REG0_OFFSET;
+    BA59 => FIOPAD_BA59_REG0_OFFSET;
+    AW57 => FIOPAD_AW57_REG0_OFFSET;
+    AW59 => FIOPAD_AW59_REG0_OFFSET;
+    AU55 => FIOPAD_AU55_REG0_OFFSET;
+    AN57 => FIOPAD_AN57_REG0_OFFSET;
+    AL59 => FIOPAD_AL59_REG0_OFFSET;
+    AJ59 => FIOPAD_AJ59_REG0_OFFSET;
+    AJ57 => FIOPAD_AJ57_REG0_OFFSET, FIOPAD_AJ57_REG1_OFFSET;
+    AG59 => FIOPAD_AG59_REG0_OFFSET, FIOPAD_AG59_REG1_OFFSET;
+    AG57 => FIOPAD_AG57_REG0_OFFSET, FIOPAD_AG57_REG1_OFFSET;
+    AE59 => FIOPAD_AE59_REG0_OFFSET, FIOPAD_AE59_REG1_OFFSET;
+    AC59 => FIOPAD_AC59_REG0_OFFSET;
+    AC57 => FIOPAD_AC57_REG0_OFFSET;
+    AR49 => FIOPAD_AR49_REG0_OFFSET;
+    BA55 => FIOPAD_BA55_REG0_OFFSET, FIOPAD_BA55_REG1_OFFSET;
+    BA53 => FIOPAD_BA53_REG0_OFFSET, FIOPAD_BA53_REG1_OFFSET;
+    AR59 => FIOPAD_AR59_REG0_OFFSET, FIOPAD_AR59_REG1_OFFSET;
+    AU59 => FIOPAD_AU59_REG0_OFFSET, FIOPAD_AU59_REG1_OFFSET;
+    AR57 => FIOPAD_AR57_REG0_OFFSET;
+    BA49 => FIOPAD_BA49_REG0_OFFSET;
+    AW55 => FIOPAD_AW55_REG0_OFFSET;
+    A35 => FIOPAD_A35_REG0_OFFSET;
+    R57 => FIOPAD_R57_REG0_OFFSET;
+    R59 => FIOPAD_R59_REG0_OFFSET;
+    U59 => FIOPAD_U59_REG0_OFFSET;
+    W59 => FIOPAD_W59_REG0_OFFSET;
+    U57 => FIOPAD_U57_REG0_OFFSET;
+    AA57 => FIOPAD_AA57_REG0_OFFSET;
+    AA59 => FIOPAD_AA59_REG0_OFFSET;
+    AW51 => FIOPAD_AW51_REG0_OFFSET;
+    AU51 => FIOPAD_AU51_REG0_OFFSET;
+    A39 => FIOPAD_A39_REG0_OFFSET;
+    C39 => FIOPAD_C39_REG0_OFFSET;
+    C37 => FIOPAD_C37_REG0_OFFSET;
+    A37 => FIOPAD_A37_REG0_OFFSET;
+    A41 => FIOPAD_A41_REG0_OFFSET;
+    A43 => FIOPAD_A43_REG0_OFFSET;
+    A45 => FIOPAD_A45_REG0_OFFSET, FIOPAD_A45_REG1_OFFSET;
+    C45 => FIOPAD_C45_REG0_OFFSET, FIOPAD_C45_REG1_OFFSET;
+    A47 => FIOPAD_A47_REG0_OFFSET, FIOPAD_A47_REG1_OFFSET;
+    A49 => FIOPAD_A49_REG0_OFFSET, FIOPAD_A49_REG1_OFFSET;
+    C49 => FIOPAD_C49_REG0_OFFSET, FIOPAD_C49_REG1_OFFSET;
+    A51 => FIOPAD_A51_REG0_OFFSET, FIOPAD_A51_REG1_OFFSET;
+    A33 => FIOPAD_A33_REG0_OFFSET, FIOPAD_A33_REG1_OFFSET;
+    C33 => FIOPAD_C33_REG0_OFFSET, FIOPAD_C33_REG1_OFFSET;
+    C31 => FIOPAD_C31_REG0_OFFSET, FIOPAD_C31_REG1_OFFSET;
+    A31 => FIOPAD_A31_REG0_OFFSET, FIOPAD_A31_REG1_OFFSET;
+    AJ53 => FIOPAD_AJ53_REG0_OFFSET, FIOPAD_AJ53_REG1_OFFSET;
+    AL49 => FIOPAD_AL49_REG0_OFFSET, FIOPAD_AL49_REG1_OFFSET;
+    AL47 => FIOPAD_AL47_REG0_OFFSET, FIOPAD_AL47_REG1_OFFSET;
+    AN49 => FIOPAD_AN49_REG0_OFFSET, FIOPAD_AN49_REG1_OFFSET;
+    AG51 => FIOPAD_AG51_REG0_OFFSET, FIOPAD_AG51_REG1_OFFSET;
+    AJ51 => FIOPAD_AJ51_REG0_OFFSET, FIOPAD_AJ51_REG1_OFFSET;
+    AG49 => FIOPAD_AG49_REG0_OFFSET, FIOPAD_AG49_REG1_OFFSET;
+    AE55 => FIOPAD_AE55_REG0_OFFSET, FIOPAD_AE55_REG1_OFFSET;
+    AE53 => FIOPAD_AE53_REG0_OFFSET, FIOPAD_AE53_REG1_OFFSET;
+    AG55 => FIOPAD_AG55_REG0_OFFSET, FIOPAD_AG55_REG1_OFFSET;
+    AJ49 => FIOPAD_AJ49_REG0_OFFSET, FIOPAD_AJ49_REG1_OFFSET;
+    AC55 => FIOPAD_AC55_REG0_OFFSET, FIOPAD_AC55_REG1_OFFSET;
+    AC53 => FIOPAD_AC53_REG0_OFFSET, FIOPAD_AC53_REG1_OFFSET;
+    AE51 => FIOPAD_AE51_REG0_OFFSET, FIOPAD_AE51_REG1_OFFSET;
+    W51 => FIOPAD_W51_REG0_OFFSET, FIOPAD_W51_REG1_OFFSET;
+    W55 => FIOPAD_W55_REG0_OFFSET;
+    W53 => FIOPAD_W53_REG0_OFFSET, FIOPAD_W53_REG1_OFFSET;
+    U55 => FIOPAD_U55_REG0_OFFSET, FIOPAD_U55_REG1_OFFSET;
+    U53 => FIOPAD_U53_REG0_OFFSET, FIOPAD_U53_REG1_OFFSET;
+    AE49 => FIOPAD_AE49_REG0_OFFSET, FIOPAD_AE49_REG1_OFFSET;
+    AC49 => FIOPAD_AC49_REG0_OFFSET, FIOPAD_AC49_REG1_OFFSET;
+    AE47 => FIOPAD_AE47_REG0_OFFSET, FIOPAD_AE47_REG1_OFFSET;
+    AA47 => FIOPAD_AA47_REG0_OFFSET, FIOPAD_AA47_REG1_OFFSET;
+    AA49 => FIOPAD_AA49_REG0_OFFSET, FIOPAD_AA49_REG1_OFFSET;
+    W49 => FIOPAD_W49_REG0_OFFSET, FIOPAD_W49_REG1_OFFSET;
+    AA51 => FIOPAD_AA51_REG0_OFFSET, FIOPAD_AA51_REG1_OFFSET;
+    U49 => FIOPAD_U49_REG0_OFFSET, FIOPAD_U49_REG1_OFFSET;
+    G59 => FIOPAD_G59_REG0_OFFSET;
+    J59 => FIOPAD_J59_REG0_OFFSET, FIOPAD_J59_REG1_OFFSET;
+    L57 => FIOPAD_L57_REG0_OFFSET, FIOPAD_L57_REG1_OFFSET;
+    C59 => FIOPAD_C59_REG0_OFFSET, FIOPAD_C59_REG1_OFFSET;
+    E59 => FIOPAD_E59_REG0_OFFSET, FIOPAD_E59_REG1_OFFSET;
+    J57 => FIOPAD_J57_REG0_OFFSET, FIOPAD_J57_REG1_OFFSET;
+    L59 => FIOPAD_L59_REG0_OFFSET, FIOPAD_L59_REG1_OFFSET;
+    N59 => FIOPAD_N59_REG0_OFFSET, FIOPAD_N59_REG1_OFFSET;
+    C57 => FIOPAD_C57_REG0_OFFSET;
+    E57 => FIOPAD_E57_REG0_OFFSET;
+    E31 => FIOPAD_E31_REG0_OFFSET, FIOPAD_E31_REG1_OFFSET;
+    G31 => FIOPAD_G31_REG0_OFFSET, FIOPAD_G31_REG1_OFFSET;
+    N41 => FIOPAD_N41_REG0_OFFSET, FIOPAD_N41_REG1_OFFSET;
+    N39 => FIOPAD_N39_REG0_OFFSET, FIOPAD_N39_REG1_OFFSET;
+    J33 => FIOPAD_J33_REG0_OFFSET, FIOPAD_J33_REG1_OFFSET;
+    N33 => FIOPAD_N33_REG0_OFFSET, FIOPAD_N33_REG1_OFFSET;
+    L33 => FIOPAD_L33_REG0_OFFSET, FIOPAD_L33_REG1_OFFSET;
+    N45 => FIOPAD_N45_REG0_OFFSET, FIOPAD_N45_REG1_OFFSET;
+    N43 => FIOPAD_N43_REG0_OFFSET, FIOPAD_N43_REG1_OFFSET;
+    L31 => FIOPAD_L31_REG0_OFFSET, FIOPAD_L31_REG1_OFFSET;
+    J31 => FIOPAD_J31_REG0_OFFSET, FIOPAD_J31_REG1_OFFSET;
+    J29 => FIOPAD_J29_REG0_OFFSET, FIOPAD_J29_REG1_OFFSET;
+    E29 => FIOPAD_E29_REG0_OFFSET, FIOPAD_E29_REG1_OFFSET;
+    G29 => FIOPAD_G29_REG0_OFFSET, FIOPAD_G29_REG1_OFFSET;
+    N27 => FIOPAD_N27_REG0_OFFSET;
+    L29 => FIOPAD_L29_REG0_OFFSET;
+    J37 => FIOPAD_J37_REG0_OFFSET, FIOPAD_J37_REG1_OFFSET;
+    J39 => FIOPAD_J39_REG0_OFFSET, FIOPAD_J39_REG1_OFFSET;
+    G41 => FIOPAD_G41_REG0_OFFSET, FIOPAD_G41_REG1_OFFSET;
+    E43 => FIOPAD_E43_REG0_OFFSET, FIOPAD_E43_REG1_OFFSET;
+    L43 => FIOPAD_L43_REG0_OFFSET, FIOPAD_L43_REG1_OFFSET;
+    C43 => FIOPAD_C43_REG0_OFFSET, FIOPAD_C43_REG1_OFFSET;
+    E41 => FIOPAD_E41_REG0_OFFSET, FIOPAD_E41_REG1_OFFSET;
+    L45 => FIOPAD_L45_REG0_OFFSET, FIOPAD_L45_REG1_OFFSET;
+    J43 => FIOPAD_J43_REG0_OFFSET, FIOPAD_J43_REG1_OFFSET;
+    J41 => FIOPAD_J41_REG0_OFFSET, FIOPAD_J41_REG1_OFFSET;
+    L39 => FIOPAD_L39_REG0_OFFSET, FIOPAD_L39_REG1_OFFSET;
+    E37 => FIOPAD_E37_REG0_OFFSET, FIOPAD_E37_REG1_OFFSET;
+    E35 => FIOPAD_E35_REG0_OFFSET, FIOPAD_E35_REG1_OFFSET;
+    G35 => FIOPAD_G35_REG0_OFFSET, FIOPAD_G35_REG1_OFFSET;
+    J35 => FIOPAD_J35_REG0_OFFSET;
+    L37 => FIOPAD_L37_REG0_OFFSET;
+    N35 => FIOPAD_N35_REG0_OFFSET;
+    R51 => FIOPAD_R51_REG0_OFFSET;
+    R49 => FIOPAD_R49_REG0_OFFSET;
+    N51 => FIOPAD_N51_REG0_OFFSET;
+    N55 => FIOPAD_N55_REG0_OFFSET;
+    L55 => FIOPAD_L55_REG0_OFFSET, FIOPAD_L55_REG1_OFFSET;
+    J55 => FIOPAD_J55_REG0_OFFSET, FIOPAD_J55_REG1_OFFSET;
+    J45 => FIOPAD_J45_REG0_OFFSET, FIOPAD_J45_REG1_OFFSET;
+    E47 => FIOPAD_E47_REG0_OFFSET, FIOPAD_E47_REG1_OFFSET;
+    G47 => FIOPAD_G47_REG0_OFFSET, FIOPAD_G47_REG1_OFFSET;
+    J47 => FIOPAD_J47_REG0_OFFSET, FIOPAD_J47_REG1_OFFSET;
+    J49 => FIOPAD_J49_REG0_OFFSET, FIOPAD_J49_REG1_OFFSET;
+    N49 => FIOPAD_N49_REG0_OFFSET, FIOPAD_N49_REG1_OFFSET;
+    L51 => FIOPAD_L51_REG0_OFFSET, FIOPAD_L51_REG1_OFFSET;
+    L49 => FIOPAD_L49_REG0_OFFSET, FIOPAD_L49_REG1_OFFSET;
+    N53 => FIOPAD_N53_REG0_OFFSET, FIOPAD_N53_REG1_OFFSET;
+    J53 => FIOPAD_J53_REG0_OFFSET, FIOPAD_J53_REG1_OFFSET;
+}
+
+impl PadId {
+    /// Look a pad up by name, ignoring ASCII case.
+    pub fn from_name(name: &str) -> Option<PadId> {
+        PadId::ALL
+            .iter()
+            .copied()
+            .find(|pad| pad.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Look a pad up by the offset of its function/pull/drive register.
+    pub fn from_reg0_offset(offset: u32) -> Option<PadId> {
+        PadId::ALL
+            .iter()
+            .copied()
+            .find(|pad| pad.reg0_offset() == offset)
+    }
+}
+
+/* field layout shared by all pads, see XReg0 / XReg1 */
+pub(crate) const FIOPAD_FUNC_MASK: u32 = genmask!(2, 0);
+pub(crate) const FIOPAD_DRIVE_MASK: u32 = genmask!(7, 4);
+pub(crate) const FIOPAD_PULL_MASK: u32 = genmask!(9, 8);
+pub(crate) const FIOPAD_REG0_CONFIG_MASK: u32 =
+    FIOPAD_FUNC_MASK | FIOPAD_DRIVE_MASK | FIOPAD_PULL_MASK;
+
+/// Encode function, pull and drive strength into register 0 field bits.
+pub(crate) fn reg0_config_bits(func: FioPadFunc, pull: FioPadPull, drive: FioPadDrive) -> u32 {
+    set_reg32_bits!(u32::from(func), 2, 0)
+        | set_reg32_bits!(u32::from(drive), 7, 4)
+        | set_reg32_bits!(u32::from(pull), 9, 8)
+}
+
+/// Bit range `(high, low)` of a delay field in register 1.
+fn delay_field(dir: &FioPadDelayDir, typ: &FioPadDelayType) -> FioPadResult<(u32, u32)> {
+    match (dir, typ) {
+        (FioPadDelayDir::OutputDelay, FioPadDelayType::DelayFineTuning) => Ok((11, 9)),
+        (FioPadDelayDir::OutputDelay, FioPadDelayType::DelayCoarseTuning) => Ok((14, 12)),
+        (FioPadDelayDir::InputDelay, FioPadDelayType::DelayFineTuning) => Ok((3, 1)),
+        (FioPadDelayDir::InputDelay, FioPadDelayType::DelayCoarseTuning) => Ok((6, 4)),
+        _ => Err(FioPadError::InvalParam),
+    }
+}
+
+/// Handle to a single pad, returned by [`IoPad::pad`](super::IoPad::pad).
+///
+/// Setters clear the field before writing it, so the previous value never
+/// leaks into the new one.
+pub struct Pad<'a> {
+    reg: &'a IoPadReg,
+    id: PadId,
+}
+
+impl<'a> Pad<'a> {
+    pub(crate) fn new(reg: &'a IoPadReg, id: PadId) -> Self {
+        Self { reg, id }
+    }
+
+    /// Get the pad identifier.
+    pub fn id(&self) -> PadId {
+        self.id
+    }
+
+    fn reg0_update(&self, mask: u32, bits: u32) {
+        let offset = self.id.reg0_offset();
+        let val = self.reg.read_32(offset);
+        self.reg.write_32(offset, (val & !mask) | (bits & mask));
+    }
+
+    fn reg1_offset(&self) -> FioPadResult<u32> {
+        self.id.reg1_offset().ok_or(FioPadError::NotNotSupport)
+    }
+
+    /// Raw value of the function/pull/drive register.
+    pub fn reg0_read(&self) -> u32 {
+        self.reg.read_32(self.id.reg0_offset())
+    }
+
+    /// Raw value of the delay register.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FioPadError::NotNotSupport` if the pad has no delay register.
+    pub fn reg1_read(&self) -> FioPadResult<u32> {
+        Ok(self.reg.read_32(self.reg1_offset()?))
+    }
+
+    /// Get the function multiplexing setting.
+    pub fn func_get(&self) -> FioPadFunc {
+        get_reg32_bits!(self.reg0_read(), 2, 0).into()
+    }
+
+    /// Set the function multiplexing setting.
+    pub fn func_set(&mut self, func: FioPadFunc) {
+        self.reg0_update(FIOPAD_FUNC_MASK, set_reg32_bits!(u32::from(func), 2, 0));
+    }
+
+    /// Get the pull resistor setting.
+    pub fn pull_get(&self) -> FioPadPull {
+        get_reg32_bits!(self.reg0_read(), 9, 8).into()
+    }
+
+    /// Set the pull resistor setting.
+    pub fn pull_set(&mut self, pull: FioPadPull) {
+        self.reg0_update(FIOPAD_PULL_MASK, set_reg32_bits!(u32::from(pull), 9, 8));
+    }
+
+    /// Get the drive strength setting.
+    pub fn drive_get(&self) -> FioPadDrive {
+        get_reg32_bits!(self.reg0_read(), 7, 4).into()
+    }
+
+    /// Set the drive strength setting.
+    pub fn drive_set(&mut self, drive: FioPadDrive) {
+        self.reg0_update(FIOPAD_DRIVE_MASK, set_reg32_bits!(u32::from(drive), 7, 4));
+    }
+
+    /// Get function, pull and drive strength settings.
+    pub fn config_get(&self) -> (FioPadFunc, FioPadPull, FioPadDrive) {
+        (self.func_get(), self.pull_get(), self.drive_get())
+    }
+
+    /// Set function, pull and drive strength in one register write.
+    pub fn config_set(&mut self, func: FioPadFunc, pull: FioPadPull, drive: FioPadDrive) {
+        self.reg0_update(FIOPAD_REG0_CONFIG_MASK, reg0_config_bits(func, pull, drive));
+    }
+
+    /// Get a delay setting.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Delay direction (input or output)
+    /// * `typ` - Delay type (coarse or fine tuning)
+    ///
+    /// # Errors
+    ///
+    /// Returns `FioPadError::NotNotSupport` if the pad has no delay register
+    /// and `FioPadError::InvalParam` for an invalid direction or type.
+    pub fn delay_get(
+        &self,
+        dir: FioPadDelayDir,
+        typ: FioPadDelayType,
+    ) -> FioPadResult<FioPadDelay> {
+        let (high, low) = delay_field(&dir, &typ)?;
+        let val = self.reg1_read()?;
+        Ok(((val & genmask!(high, low)) >> low).into())
+    }
+
+    /// Set a delay setting.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Delay direction (input or output)
+    /// * `typ` - Delay type (coarse or fine tuning)
+    /// * `delay` - Delay value to set
+    ///
+    /// # Errors
+    ///
+    /// See [`delay_get`](Self::delay_get).
+    pub fn delay_set(
+        &mut self,
+        dir: FioPadDelayDir,
+        typ: FioPadDelayType,
+        delay: FioPadDelay,
+    ) -> FioPadResult {
+        let (high, low) = delay_field(&dir, &typ)?;
+        let offset = self.reg1_offset()?;
+        let mask = genmask!(high, low);
+        let val = self.reg.read_32(offset);
+        self.reg
+            .write_32(offset, (val & !mask) | ((u32::from(delay) << low) & mask));
+        Ok(())
+    }
+
+    /// Enable or disable the delay line of one direction.
+    ///
+    /// # Errors
+    ///
+    /// See [`delay_get`](Self::delay_get).
+    pub fn delay_enable_set(&mut self, dir: FioPadDelayDir, enable: bool) -> FioPadResult {
+        let bit = match dir {
+            FioPadDelayDir::OutputDelay => 1 << 8,
+            FioPadDelayDir::InputDelay => 1 << 0,
+            _ => return Err(FioPadError::InvalParam),
+        };
+        let offset = self.reg1_offset()?;
+        let val = self.reg.read_32(offset);
+        self.reg
+            .write_32(offset, if enable { val | bit } else { val & !bit });
+        Ok(())
+    }
+}
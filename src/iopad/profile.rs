@@ -0,0 +1,199 @@
+//! # MCI Pin Profiles
+//!
+//! Declarative description of every pad used by an MCI instance, so the
+//! function mux, pulls and drive strengths can be programmed by the driver
+//! instead of relying on the bootloader.
+//!
+//! Only one pinout is built in, the one of the Phytium Pi board. Its CLK
+//! pads are the ones `mci_timing` tunes the output delay on (AJ49 for MCI0,
+//! J53 for MCI1); the CMD and DAT pads are assumed from the SD pad groups of
+//! the same instance and have not been checked against a datasheet for any
+//! other board or E2000 variant. Boards that route the card differently
+//! build their own [`MCIPinProfile`] from [`MCIPadSetting`]s.
+
+use super::constants::*;
+use super::pad::{PadId, reg0_config_bits};
+use crate::mci::constants::MCIId;
+
+/// SD/MMC bus signal carried by a pad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MCIPadSignal {
+    /// Card clock
+    Clk,
+    /// Command line
+    Cmd,
+    /// Data line 0-7
+    Dat(u8),
+}
+
+/// Function, pull and drive strength of one pad.
+#[derive(Debug, Clone, Copy)]
+pub struct MCIPadSetting {
+    /// Signal routed through the pad
+    pub signal: MCIPadSignal,
+    /// Pad carrying the signal
+    pub pad: PadId,
+    /// Function mux selecting the MCI signal
+    pub func: FioPadFunc,
+    /// Pull resistor
+    pub pull: FioPadPull,
+    /// Drive strength
+    pub drive: FioPadDrive,
+}
+
+impl MCIPadSetting {
+    const fn new(signal: MCIPadSignal, pad: PadId, pull: FioPadPull, drive: FioPadDrive) -> Self {
+        Self {
+            signal,
+            pad,
+            func: FioPadFunc::Func0,
+            pull,
+            drive,
+        }
+    }
+
+    /// Value the pad's function/pull/drive fields should hold.
+    pub(crate) fn reg0_bits(&self) -> u32 {
+        reg0_config_bits(self.func, self.pull, self.drive)
+    }
+}
+
+/// All pads used by one MCI instance.
+#[derive(Debug, Clone, Copy)]
+pub struct MCIPinProfile {
+    /// MCI instance the profile belongs to
+    pub instance_id: MCIId,
+    /// Pad settings, CLK first
+    pub pads: &'static [MCIPadSetting],
+}
+
+/* CLK is a push-pull output without pull, CMD and DAT idle high on the bus */
+const CLK_DRIVE: FioPadDrive = FioPadDrive::Drv2;
+const BUS_DRIVE: FioPadDrive = FioPadDrive::Drv2;
+
+const PHYTIUM_PI_MCI0_PADS: [MCIPadSetting; 10] = [
+    MCIPadSetting::new(
+        MCIPadSignal::Clk,
+        PadId::AJ49,
+        FioPadPull::PullNone,
+        CLK_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Cmd,
+        PadId::AG55,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(0),
+        PadId::AE53,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(1),
+        PadId::AE55,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(2),
+        PadId::AC55,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(3),
+        PadId::AC53,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(4),
+        PadId::AG49,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(5),
+        PadId::AJ51,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(6),
+        PadId::AG51,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(7),
+        PadId::AN49,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+];
+
+const PHYTIUM_PI_MCI1_PADS: [MCIPadSetting; 6] = [
+    MCIPadSetting::new(
+        MCIPadSignal::Clk,
+        PadId::J53,
+        FioPadPull::PullNone,
+        CLK_DRIVE,
+    ),
+    MCIPadSetting::new(MCIPadSignal::Cmd, PadId::N53, FioPadPull::PullUp, BUS_DRIVE),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(0),
+        PadId::L49,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(1),
+        PadId::L51,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(2),
+        PadId::N49,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+    MCIPadSetting::new(
+        MCIPadSignal::Dat(3),
+        PadId::J49,
+        FioPadPull::PullUp,
+        BUS_DRIVE,
+    ),
+];
+
+static PHYTIUM_PI_PROFILES: [MCIPinProfile; 2] = [
+    MCIPinProfile {
+        instance_id: MCIId::MCI0,
+        pads: &PHYTIUM_PI_MCI0_PADS,
+    },
+    MCIPinProfile {
+        instance_id: MCIId::MCI1,
+        pads: &PHYTIUM_PI_MCI1_PADS,
+    },
+];
+
+impl MCIPinProfile {
+    /// Get the built-in profile of an MCI instance, the Phytium Pi pinout.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - MCI instance
+    pub fn lookup(instance_id: MCIId) -> &'static MCIPinProfile {
+        match instance_id {
+            MCIId::MCI0 => &PHYTIUM_PI_PROFILES[0],
+            MCIId::MCI1 => &PHYTIUM_PI_PROFILES[1],
+        }
+    }
+
+    /// Get the setting of the pad carrying `signal`, if the profile has one.
+    pub fn pad(&self, signal: MCIPadSignal) -> Option<&MCIPadSetting> {
+        self.pads.iter().find(|pad| pad.signal == signal)
+    }
+}
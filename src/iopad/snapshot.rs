@@ -0,0 +1,198 @@
+//! # Pad Configuration Snapshots
+//!
+//! Capture the function, pull, drive and delay settings of a set of pads,
+//! compare two captures and write one back. Snapshots serialize to a small
+//! binary format so a capture from a working board can be stored and
+//! compared against a failing one.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::constants::*;
+use super::err::*;
+use super::pad::{FIOPAD_REG0_CONFIG_MASK, PadId};
+
+/// Mask of the delay enable and delay value fields in register 1.
+pub(crate) const FIOPAD_REG1_DELAY_MASK: u32 = genmask!(14, 0);
+
+/// Serialization header: magic and format version.
+const SNAPSHOT_MAGIC: [u8; 4] = *b"PADS";
+const SNAPSHOT_VERSION: u8 = 1;
+const SNAPSHOT_HEADER_LEN: usize = 7;
+const SNAPSHOT_ENTRY_LEN: usize = 13;
+
+/// Register contents of one pad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PadState {
+    /// Pad the values belong to
+    pub pad: PadId,
+    /// Function/pull/drive fields of register 0
+    pub reg0: u32,
+    /// Delay fields of register 1, `None` if the pad has no delay register
+    pub reg1: Option<u32>,
+}
+
+impl PadState {
+    /// Function multiplexing setting
+    pub fn func(&self) -> FioPadFunc {
+        get_reg32_bits!(self.reg0, 2, 0).into()
+    }
+
+    /// Pull resistor setting
+    pub fn pull(&self) -> FioPadPull {
+        get_reg32_bits!(self.reg0, 9, 8).into()
+    }
+
+    /// Drive strength setting
+    pub fn drive(&self) -> FioPadDrive {
+        get_reg32_bits!(self.reg0, 7, 4).into()
+    }
+
+    /// Delay settings as `(out enable, out coarse, out fine, in enable, in coarse, in fine)`
+    pub fn delays(&self) -> Option<(bool, u32, u32, bool, u32, u32)> {
+        self.reg1.map(|reg1| {
+            (
+                reg1 & (1 << 8) != 0,
+                get_reg32_bits!(reg1, 14, 12),
+                get_reg32_bits!(reg1, 11, 9),
+                reg1 & (1 << 0) != 0,
+                get_reg32_bits!(reg1, 6, 4),
+                get_reg32_bits!(reg1, 3, 1),
+            )
+        })
+    }
+}
+
+impl fmt::Display for PadState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: func {} pull {} drive {}",
+            self.pad.name(),
+            get_reg32_bits!(self.reg0, 2, 0),
+            get_reg32_bits!(self.reg0, 9, 8),
+            get_reg32_bits!(self.reg0, 7, 4)
+        )?;
+        if let Some((out_en, out_coarse, out_fine, in_en, in_coarse, in_fine)) = self.delays() {
+            write!(
+                f,
+                ", out {}{}.{}, in {}{}.{}",
+                if out_en { "" } else { "off " },
+                out_coarse,
+                out_fine,
+                if in_en { "" } else { "off " },
+                in_coarse,
+                in_fine
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A pad whose settings differ between two snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PadDiff {
+    /// Settings in the snapshot `diff` was called on
+    pub before: PadState,
+    /// Settings in the snapshot passed to `diff`
+    pub after: PadState,
+}
+
+impl fmt::Display for PadDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.before, self.after)
+    }
+}
+
+/// Settings of a set of pads, captured with [`IoPad::snapshot`](super::IoPad::snapshot).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IoPadSnapshot {
+    pads: Vec<PadState>,
+}
+
+impl IoPadSnapshot {
+    pub(crate) fn from_states(pads: Vec<PadState>) -> Self {
+        Self { pads }
+    }
+
+    /// Captured pads in capture order
+    pub fn pads(&self) -> &[PadState] {
+        &self.pads
+    }
+
+    /// Settings of one pad, if it was captured
+    pub fn get(&self, pad: PadId) -> Option<&PadState> {
+        self.pads.iter().find(|state| state.pad == pad)
+    }
+
+    /// List pads whose settings differ from `other`.
+    ///
+    /// Pads captured in only one of the snapshots are ignored.
+    pub fn diff(&self, other: &IoPadSnapshot) -> Vec<PadDiff> {
+        self.pads
+            .iter()
+            .filter_map(|before| {
+                let after = other.get(before.pad)?;
+                (before != after).then_some(PadDiff {
+                    before: *before,
+                    after: *after,
+                })
+            })
+            .collect()
+    }
+
+    /// Serialize into a compact little-endian binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(SNAPSHOT_HEADER_LEN + SNAPSHOT_ENTRY_LEN * self.pads.len());
+        bytes.extend_from_slice(&SNAPSHOT_MAGIC);
+        bytes.push(SNAPSHOT_VERSION);
+        bytes.extend_from_slice(&(self.pads.len() as u16).to_le_bytes());
+        for state in &self.pads {
+            bytes.extend_from_slice(&state.pad.reg0_offset().to_le_bytes());
+            bytes.extend_from_slice(&state.reg0.to_le_bytes());
+            bytes.push(state.reg1.is_some() as u8);
+            bytes.extend_from_slice(&state.reg1.unwrap_or(0).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Parse a snapshot produced by [`to_bytes`](Self::to_bytes).
+    ///
+    /// # Errors
+    ///
+    /// Returns `FioPadError::InvalParam` if the data is truncated, has an
+    /// unknown header or names a pad that doesn't exist.
+    pub fn from_bytes(bytes: &[u8]) -> FioPadResult<Self> {
+        if bytes.len() < SNAPSHOT_HEADER_LEN
+            || bytes[..4] != SNAPSHOT_MAGIC
+            || bytes[4] != SNAPSHOT_VERSION
+        {
+            return Err(FioPadError::InvalParam);
+        }
+        let count = u16::from_le_bytes([bytes[5], bytes[6]]) as usize;
+        let entries = &bytes[SNAPSHOT_HEADER_LEN..];
+        if entries.len() != count * SNAPSHOT_ENTRY_LEN {
+            return Err(FioPadError::InvalParam);
+        }
+
+        let word = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        let mut pads = Vec::with_capacity(count);
+        for entry in entries.chunks_exact(SNAPSHOT_ENTRY_LEN) {
+            let pad = PadId::from_reg0_offset(word(&entry[0..4])).ok_or(FioPadError::InvalParam)?;
+            let reg1 = match entry[8] {
+                0 => None,
+                _ => Some(word(&entry[9..13])),
+            };
+            if reg1.is_some() != pad.reg1_offset().is_some() {
+                return Err(FioPadError::InvalParam);
+            }
+            pads.push(PadState {
+                pad,
+                reg0: word(&entry[4..8]) & FIOPAD_REG0_CONFIG_MASK,
+                reg1: reg1.map(|reg1| reg1 & FIOPAD_REG1_DELAY_MASK),
+            });
+        }
+        Ok(Self { pads })
+    }
+}
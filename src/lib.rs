@@ -27,7 +27,7 @@
 //! ## Usage
 //!
 //! The driver requires the user to implement the [`Kernel`] trait and use the
-//! [`set_impl!`] macro to provide sleep functionality.
+//! [`set_impl!`] macro to provide sleep and clock functionality.
 //!
 //! ```rust
 //! use phytium_mci::{Kernel, set_impl};
@@ -38,6 +38,11 @@
 //!     fn sleep(duration: core::time::Duration) {
 //!         // Implement sleep functionality
 //!     }
+//!
+//!     fn now() -> core::time::Duration {
+//!         // Return a monotonic timestamp
+//!         core::time::Duration::ZERO
+//!     }
 //! }
 //!
 //! set_impl!(MyKernel);
@@ -47,6 +52,7 @@
 
 extern crate alloc;
 
+use core::sync::atomic::{AtomicBool, Ordering};
 use core::time::Duration;
 
 #[macro_use]
@@ -65,12 +71,69 @@ pub use mci_host::*;
 /// Trait that must be implemented by the user to provide kernel functionality.
 ///
 /// This trait abstracts the underlying kernel/OS operations required by the driver.
-/// Currently, only a sleep function is required for timing operations.
+/// A sleep function and a monotonic clock are required for timing operations.
 pub trait Kernel {
     /// Sleep for the specified duration.
     ///
     /// This is used internally for delay operations and timing control.
     fn sleep(duration: Duration);
+
+    /// Get the current time of a monotonic clock.
+    ///
+    /// The epoch is arbitrary (e.g. time since boot), only differences between
+    /// two readings are used. All driver timeouts are measured against this clock.
+    fn now() -> Duration;
+
+    /// Give the CPU to other tasks while the driver waits.
+    ///
+    /// Defaults to a spin-loop hint, which is right for bare-metal use.
+    fn yield_now() {
+        core::hint::spin_loop();
+    }
+
+    /// Acquire the mutex identified by `lock`.
+    ///
+    /// `lock` is owned by the driver and only passed to [`mutex_lock`](Kernel::mutex_lock)
+    /// and [`mutex_unlock`](Kernel::mutex_unlock); an implementation may use it as
+    /// state or use its address as the key of an OS mutex. The default spins on it,
+    /// calling [`yield_now`](Kernel::yield_now) while contended.
+    fn mutex_lock(lock: &AtomicBool) {
+        while lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            Self::yield_now();
+        }
+    }
+
+    /// Release the mutex identified by `lock`.
+    fn mutex_unlock(lock: &AtomicBool) {
+        lock.store(false, Ordering::Release);
+    }
+
+    /// Block until the event identified by `event` is notified or `timeout` passes.
+    ///
+    /// A notification is consumed by the wait that sees it. Returns `false` on
+    /// timeout. As with the mutex, `event` may be used as state or as a key.
+    fn event_wait(event: &AtomicBool, timeout: Duration) -> bool {
+        let deadline = Self::now().saturating_add(timeout);
+        loop {
+            if event.swap(false, Ordering::AcqRel) {
+                return true;
+            }
+            if Self::now() >= deadline {
+                return false;
+            }
+            Self::yield_now();
+        }
+    }
+
+    /// Notify the event identified by `event`, waking one waiter.
+    ///
+    /// May be called from interrupt context.
+    fn event_notify(event: &AtomicBool) {
+        event.store(true, Ordering::Release);
+    }
 }
 
 pub(crate) fn sleep(duration: Duration) {
@@ -83,10 +146,60 @@ pub(crate) fn sleep(duration: Duration) {
     }
 }
 
+pub(crate) fn now() -> Duration {
+    unsafe extern "Rust" {
+        fn _phytium_mci_now() -> Duration;
+    }
+
+    unsafe { _phytium_mci_now() }
+}
+
+pub(crate) fn yield_now() {
+    unsafe extern "Rust" {
+        fn _phytium_mci_yield_now();
+    }
+
+    unsafe { _phytium_mci_yield_now() }
+}
+
+pub(crate) fn mutex_lock(lock: &AtomicBool) {
+    unsafe extern "Rust" {
+        fn _phytium_mci_mutex_lock(lock: &AtomicBool);
+    }
+
+    unsafe { _phytium_mci_mutex_lock(lock) }
+}
+
+pub(crate) fn mutex_unlock(lock: &AtomicBool) {
+    unsafe extern "Rust" {
+        fn _phytium_mci_mutex_unlock(lock: &AtomicBool);
+    }
+
+    unsafe { _phytium_mci_mutex_unlock(lock) }
+}
+
+pub(crate) fn event_wait(event: &AtomicBool, timeout: Duration) -> bool {
+    unsafe extern "Rust" {
+        fn _phytium_mci_event_wait(event: &AtomicBool, timeout: Duration) -> bool;
+    }
+
+    unsafe { _phytium_mci_event_wait(event, timeout) }
+}
+
+pub(crate) fn event_notify(event: &AtomicBool) {
+    unsafe extern "Rust" {
+        fn _phytium_mci_event_notify(event: &AtomicBool);
+    }
+
+    unsafe { _phytium_mci_event_notify(event) }
+}
+
 /// Macro to set the kernel implementation for the driver.
 ///
-/// This macro generates the internal function that bridges the driver's sleep
-/// calls to the user-provided [`Kernel`] implementation.
+/// This macro generates the internal functions that bridge the driver's sleep,
+/// clock, yield, mutex and event calls to the user-provided [`Kernel`]
+/// implementation. Methods the implementation doesn't override use the
+/// trait's spin-based defaults.
 ///
 /// # Example
 ///
@@ -98,6 +211,11 @@ pub(crate) fn sleep(duration: Duration) {
 ///     fn sleep(duration: core::time::Duration) {
 ///         // implementation
 ///     }
+///
+///     fn now() -> core::time::Duration {
+///         // implementation
+///         core::time::Duration::ZERO
+///     }
 /// }
 ///
 /// set_impl!(MyKernel);
@@ -106,8 +224,41 @@ pub(crate) fn sleep(duration: Duration) {
 macro_rules! set_impl {
     ($t: ty) => {
         #[unsafe(no_mangle)]
-        unsafe fn _phytium_mci_sleep(duration: Duration) {
+        unsafe fn _phytium_mci_sleep(duration: ::core::time::Duration) {
             <$t as $crate::Kernel>::sleep(duration)
         }
+
+        #[unsafe(no_mangle)]
+        unsafe fn _phytium_mci_now() -> ::core::time::Duration {
+            <$t as $crate::Kernel>::now()
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe fn _phytium_mci_yield_now() {
+            <$t as $crate::Kernel>::yield_now()
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe fn _phytium_mci_mutex_lock(lock: &::core::sync::atomic::AtomicBool) {
+            <$t as $crate::Kernel>::mutex_lock(lock)
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe fn _phytium_mci_mutex_unlock(lock: &::core::sync::atomic::AtomicBool) {
+            <$t as $crate::Kernel>::mutex_unlock(lock)
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe fn _phytium_mci_event_wait(
+            event: &::core::sync::atomic::AtomicBool,
+            timeout: ::core::time::Duration,
+        ) -> bool {
+            <$t as $crate::Kernel>::event_wait(event, timeout)
+        }
+
+        #[unsafe(no_mangle)]
+        unsafe fn _phytium_mci_event_notify(event: &::core::sync::atomic::AtomicBool) {
+            <$t as $crate::Kernel>::event_notify(event)
+        }
     };
 }
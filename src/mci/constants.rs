@@ -9,6 +9,7 @@
 //! - DMA descriptors
 
 use core::arch::asm;
+use core::time::Duration;
 
 use bitflags::bitflags;
 
@@ -258,6 +259,8 @@ pub const FSDIF_UHS_REG_OFFSET: u32 = 0x74;
 pub const FSDIF_CARD_RESET_OFFSET: u32 = 0x78;
 /// Bus mode register
 pub const FSDIF_BUS_MODE_OFFSET: u32 = 0x80;
+/// Poll demand register
+pub const FSDIF_POLL_DEMAND_OFFSET: u32 = 0x84;
 /// Descriptor list low base address register
 pub const FSDIF_DESC_LIST_ADDRL_OFFSET: u32 = 0x88;
 /// Descriptor list high base address register
@@ -285,12 +288,18 @@ pub const FSDIF_ENABLE_SHIFT_OFFSET: u32 = 0x110;
 /// Data FIFO access
 pub const FSDIF_DATA_OFFSET: u32 = 0x200;
 
-/// Timeout for retry operations
-pub const RETRIES_TIMEOUT: usize = 50000;
+/// Default time to wait for a command to complete
+pub const FSDIF_CMD_TIMEOUT: Duration = Duration::from_millis(100);
+/// Default time to wait for a data transfer or a busy card to complete
+pub const FSDIF_DATA_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Time the IDMAC gets to hand the descriptors back after the data is over
+pub const FSDIF_DMA_DESC_RECLAIM_TIMEOUT: Duration = Duration::from_millis(1);
 /// Delay in microseconds
 pub const FSDIF_DELAY_US: u32 = 5;
 /// Maximum FIFO count
 pub const MCI_MAX_FIFO_CNT: u32 = 0x800;
+/// Number of commands kept by the command trace
+pub const MCI_CMD_TRACE_DEPTH: usize = 32;
 
 /// Maximum command retries
 pub const FSL_SDMMC_MAX_CMD_RETRIES: u32 = 10;
@@ -3,9 +3,10 @@
 //! This module defines error types for MCI controller operations.
 
 use super::RegError;
+use super::regs::{MCIDMACStatus, MCIRawInts};
 
 /// MCI controller error enumeration.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MCIError {
     /// Operation timeout
     Timeout,
@@ -29,6 +30,76 @@ pub enum MCIError {
     DmaBufUnalign,
     /// Invalid timing configuration
     InvalidTiming,
+    /// Response error (RE)
+    RespError,
+    /// Response CRC error (RCRC)
+    RespCrc,
+    /// Response timeout (RTO)
+    RespTimeout,
+    /// Data CRC error (DCRC)
+    DataCrc,
+    /// Data read timeout (DRTO)
+    DataTimeout,
+    /// Data starvation-by-host timeout (HTO)
+    HostTimeout,
+    /// FIFO underrun/overrun (FRUN)
+    Fifo,
+    /// Hardware locked write error (HLE)
+    HardwareLocked,
+    /// Start-bit error (SBE)
+    StartBit,
+    /// End-bit error on read / missing CRC status on write (EBE)
+    EndBit,
+    /// Internal DMA fatal bus error (FBE)
+    DmaBusError,
+    /// Internal DMA ran into a descriptor it doesn't own (DU)
+    DmaDescUnavailable,
+    /// Internal DMA card error summary (CES)
+    DmaCardError,
+    /// Internal DMA kept a descriptor after the transfer was over
+    DmaDescOwned,
+}
+
+impl MCIError {
+    /// Decode the error bits of the raw interrupt status.
+    ///
+    /// Response errors are reported before data errors, since a failed
+    /// command makes any data error a consequence rather than the cause.
+    /// Returns `None` if no error bit is set.
+    pub fn from_raw_ints(raw_ints: u32) -> Option<MCIError> {
+        const ERRORS: [(u32, MCIError); 10] = [
+            (MCIRawInts::RTO_BIT.bits(), MCIError::RespTimeout),
+            (MCIRawInts::RCRC_BIT.bits(), MCIError::RespCrc),
+            (MCIRawInts::RE_BIT.bits(), MCIError::RespError),
+            (MCIRawInts::HLE_BIT.bits(), MCIError::HardwareLocked),
+            (MCIRawInts::DRTO_BIT.bits(), MCIError::DataTimeout),
+            (MCIRawInts::DCRC_BIT.bits(), MCIError::DataCrc),
+            (MCIRawInts::SBE_BCI_BIT.bits(), MCIError::StartBit),
+            (MCIRawInts::EBE_BIT.bits(), MCIError::EndBit),
+            (MCIRawInts::FRUN_BIT.bits(), MCIError::Fifo),
+            (MCIRawInts::HTO_BIT.bits(), MCIError::HostTimeout),
+        ];
+        ERRORS
+            .iter()
+            .find(|(bit, _)| raw_ints & bit != 0)
+            .map(|(_, err)| *err)
+    }
+
+    /// Decode the error bits of the internal DMA status.
+    ///
+    /// Returns `None` if no error bit is set.
+    pub fn from_dma_status(dma_status: u32) -> Option<MCIError> {
+        let dma_status = MCIDMACStatus::from_bits_truncate(dma_status);
+        if dma_status.contains(MCIDMACStatus::FBE) {
+            Some(MCIError::DmaBusError)
+        } else if dma_status.intersects(MCIDMACStatus::DU_BIT0 | MCIDMACStatus::DU_BIT1) {
+            Some(MCIError::DmaDescUnavailable)
+        } else if dma_status.contains(MCIDMACStatus::CES) {
+            Some(MCIError::DmaCardError)
+        } else {
+            None
+        }
+    }
 }
 
 impl RegError for MCIError {
@@ -1,5 +1,3 @@
-use core::time::Duration;
-
 use log::*;
 
 use crate::sleep;
@@ -14,9 +12,9 @@ impl MCI {
     pub(crate) fn private_cmd_send(&self, cmd: MCICmd, arg: u32) -> MCIResult {
         let reg = self.config.reg();
 
-        reg.retry_for(
+        reg.poll_until(
             |reg: MCIStatus| !reg.contains(MCIStatus::DATA_BUSY),
-            Some(RETRIES_TIMEOUT),
+            self.config.data_timeout(),
         )?;
         reg.write_reg(MCICmdArg::from_bits_truncate(arg));
 
@@ -25,9 +23,9 @@ impl MCI {
         let cmd_reg = MCICmd::START | cmd;
 
         reg.write_reg(cmd_reg);
-        reg.retry_for(
+        reg.poll_until(
             |reg: MCICmd| !reg.contains(MCICmd::START),
-            Some(RETRIES_TIMEOUT),
+            self.config.cmd_timeout(),
         )?;
 
         Ok(())
@@ -37,16 +35,16 @@ impl MCI {
         let reg = self.config.reg();
         unsafe { dsb() }; /* drain writebuffer */
         reg.write_reg(MCICmd::START | cmd);
-        reg.retry_for(
+        reg.poll_until(
             |reg| (MCICmd::START & reg).bits() == 0,
-            Some(RETRIES_TIMEOUT),
+            self.config.cmd_timeout(),
         )?;
         Ok(())
     }
 
-    pub(crate) fn cmd_transfer(&self, cmd_data: &MCICmdData) -> MCIResult {
+    /// The command register value for `cmd_data`, without the START bit.
+    pub(crate) fn cmd_raw_get(&self, cmd_data: &MCICmdData) -> MCICmd {
         let mut raw_cmd = MCICmd::empty();
-        let reg = self.config.reg();
 
         if self.curr_timing.use_hold() {
             raw_cmd |= MCICmd::USE_HOLD_REG;
@@ -70,6 +68,10 @@ impl MCI {
             if flag.contains(MCICmdFlag::WRITE_DATA) {
                 raw_cmd |= MCICmd::DAT_WRITE;
             }
+            /* Controller sends CMD12 itself once the last block is over */
+            if flag.contains(MCICmdFlag::NEED_AUTO_STOP) {
+                raw_cmd |= MCICmd::SEND_AUTO_STOP;
+            }
         }
         /* Command requires CRC check */
         if flag.contains(MCICmdFlag::NEED_RESP_CRC) {
@@ -83,6 +85,12 @@ impl MCI {
             }
         }
         raw_cmd |= MCICmd::from_bits_truncate(set_reg32_bits!(cmd_data.cmdidx(), 5, 0));
+        raw_cmd
+    }
+
+    pub(crate) fn cmd_transfer(&self, cmd_data: &MCICmdData) -> MCIResult {
+        let raw_cmd = self.cmd_raw_get(cmd_data);
+        let reg = self.config.reg();
         debug!(
             "============[{}-{}]@0x{:x} begin ============",
             {
@@ -108,22 +116,11 @@ impl MCI {
     }
 
     pub(crate) fn cmd_response_get(&mut self, cmd_data: &mut MCICmdData) -> MCIResult {
-        #[cfg(feature = "pio")]
-        let read = cmd_data.flag().contains(MCICmdFlag::READ_DATA);
-
         if !self.is_ready {
             error!("device is not yet initialized!!!");
             return Err(MCIError::NotInit);
         }
 
-        #[cfg(feature = "pio")]
-        if let Some(data) = cmd_data.get_mut_data()
-            && read
-            && MCITransMode::PIO == self.config.trans_mode()
-        {
-            self.pio_read_data(data)?;
-        }
-
         /* check response of cmd */
         let flag = *cmd_data.flag();
         let reg = self.config.reg();
@@ -147,9 +144,34 @@ impl MCI {
             }
         }
 
+        /* the controller keeps the response of its own CMD12 apart */
+        if flag.contains(MCICmdFlag::NEED_AUTO_STOP) {
+            trace!(
+                "    auto stop resp: 0x{:x}",
+                reg.read_reg::<MCIResp1>().bits()
+            );
+        }
+
+        /* errors raised while draining the FIFO or after the poll acked the status */
+        if let Some(err) = self.transfer_error_get() {
+            let raw_ints = self.raw_status_get().bits();
+            error!(
+                "CMD-{} failed: {:?}, raw ints: 0x{:x}",
+                cmd_data.cmdidx(),
+                err,
+                raw_ints
+            );
+            cmd_data.raw_ints_set(raw_ints);
+            self.raw_status_clear();
+            return Err(err);
+        }
+
         cmd_data.success_set(true); /* cmd / data transfer finished successful */
 
-        sleep(Duration::from_micros(50)); // Allow some time for the command to settle
+        let settle_delay = self.config.cmd_settle_delay();
+        if !settle_delay.is_zero() {
+            sleep(settle_delay);
+        }
 
         debug!(
             "============[{}-{}]@0x{:x} end ============",
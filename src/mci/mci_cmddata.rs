@@ -23,6 +23,8 @@ pub struct MCICmdData {
     data: Option<MCIData>,
     /// Transfer success status
     success: bool,
+    /// Raw interrupt status latched when the transfer failed, or at completion with `cmd-trace`
+    raw_ints: u32,
 }
 
 #[allow(unused)]
@@ -35,6 +37,7 @@ impl MCICmdData {
             flag: MCICmdFlag::empty(),
             data: None,
             success: false,
+            raw_ints: 0,
         }
     }
 
@@ -45,6 +48,7 @@ impl MCICmdData {
         self.flag = MCICmdFlag::empty();
         self.data = None;
         self.success = false;
+        self.raw_ints = 0;
     }
 
     pub(crate) fn success(&self) -> bool {
@@ -55,6 +59,14 @@ impl MCICmdData {
         self.success = success;
     }
 
+    pub(crate) fn raw_ints(&self) -> u32 {
+        self.raw_ints
+    }
+
+    pub(crate) fn raw_ints_set(&mut self, raw_ints: u32) {
+        self.raw_ints = raw_ints;
+    }
+
     pub(crate) fn cmdidx(&self) -> u32 {
         self.cmdidx
     }
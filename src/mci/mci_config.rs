@@ -11,6 +11,7 @@
 compile_error!("can't enable feature dma and pio at the same time!");
 
 use core::ptr::NonNull;
+use core::time::Duration;
 
 use super::constants::*;
 use super::mci_timing::*;
@@ -31,6 +32,12 @@ pub struct MCIConfig {
     trans_mode: MCITransMode,
     /// Non-removable media flag (e.g., eMMC)
     non_removable: bool,
+    /// Time to wait for a command to complete
+    cmd_timeout: Duration,
+    /// Time to wait for a data transfer or a busy card to complete
+    data_timeout: Duration,
+    /// Delay after every command, for cards that need time before the next one
+    cmd_settle_delay: Duration,
 }
 
 impl MCIConfig {
@@ -51,6 +58,9 @@ impl MCIConfig {
             irq_num: 105,
             trans_mode: MCITransMode::DMA,
             non_removable: false,
+            cmd_timeout: FSDIF_CMD_TIMEOUT,
+            data_timeout: FSDIF_DATA_TIMEOUT,
+            cmd_settle_delay: Duration::ZERO,
         }
     }
 
@@ -71,6 +81,9 @@ impl MCIConfig {
             irq_num: 104,
             trans_mode: MCITransMode::PIO,
             non_removable: false,
+            cmd_timeout: FSDIF_CMD_TIMEOUT,
+            data_timeout: FSDIF_DATA_TIMEOUT,
+            cmd_settle_delay: Duration::ZERO,
         }
     }
 
@@ -150,4 +163,49 @@ impl MCIConfig {
     pub fn instance_id(&self) -> MCIId {
         self.instance_id
     }
+
+    /// Get the time to wait for a command to complete.
+    pub fn cmd_timeout(&self) -> Duration {
+        self.cmd_timeout
+    }
+
+    /// Set the time to wait for a command to complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time between issuing a command and its response
+    pub fn cmd_timeout_set(&mut self, timeout: Duration) {
+        self.cmd_timeout = timeout;
+    }
+
+    /// Get the time to wait for a data transfer or a busy card to complete.
+    pub fn data_timeout(&self) -> Duration {
+        self.data_timeout
+    }
+
+    /// Set the time to wait for a data transfer or a busy card to complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time for the data phase of one transfer
+    pub fn data_timeout_set(&mut self, timeout: Duration) {
+        self.data_timeout = timeout;
+    }
+
+    /// Get the delay inserted after every command.
+    pub fn cmd_settle_delay(&self) -> Duration {
+        self.cmd_settle_delay
+    }
+
+    /// Set a delay inserted after every command.
+    ///
+    /// Zero by default. Only needed for cards that misbehave when the next
+    /// command follows a response immediately.
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` - Time to wait after each command completes
+    pub fn cmd_settle_delay_set(&mut self, delay: Duration) {
+        self.cmd_settle_delay = delay;
+    }
 }
@@ -4,6 +4,9 @@
 
 use alloc::vec::Vec;
 
+#[cfg(feature = "dma")]
+use super::mci_dma::MCIDmaSegment;
+
 /// MCI data transfer structure.
 ///
 /// This structure contains information about data transfers including
@@ -16,6 +19,9 @@ pub(crate) struct MCIData {
     /// Data buffer DMA address
     #[cfg(feature = "dma")]
     buf_dma: usize,
+    /// Scatter list used instead of `buf_dma` when not empty
+    #[cfg(feature = "dma")]
+    segments: Vec<MCIDmaSegment>,
     /// Block size
     blksz: u32,
     /// Block count
@@ -30,6 +36,8 @@ impl MCIData {
             buf: None,
             #[cfg(feature = "dma")]
             buf_dma: 0,
+            #[cfg(feature = "dma")]
+            segments: Vec::new(),
             blksz: 0,
             blkcnt: 0,
             datalen: 0,
@@ -64,6 +72,7 @@ impl MCIData {
         self.buf.as_ref()
     }
 
+    #[cfg(feature = "pio")]
     pub(crate) fn buf_mut(&mut self) -> Option<&mut Vec<u32>> {
         self.buf.as_mut()
     }
@@ -72,6 +81,11 @@ impl MCIData {
         self.buf = buf
     }
 
+    #[cfg(feature = "dma")]
+    pub(crate) fn buf_take(&mut self) -> Option<Vec<u32>> {
+        self.buf.take()
+    }
+
     #[cfg(feature = "dma")]
     pub(crate) fn buf_dma(&self) -> usize {
         self.buf_dma
@@ -81,4 +95,14 @@ impl MCIData {
     pub(crate) fn buf_dma_set(&mut self, buf_dma: usize) {
         self.buf_dma = buf_dma;
     }
+
+    #[cfg(feature = "dma")]
+    pub(crate) fn segments(&self) -> &[MCIDmaSegment] {
+        &self.segments
+    }
+
+    #[cfg(feature = "dma")]
+    pub(crate) fn segments_set(&mut self, segments: Vec<MCIDmaSegment>) {
+        self.segments = segments;
+    }
 }
@@ -1,5 +1,5 @@
 use alloc::vec::Vec;
-use dma_api::DSlice;
+use dma_api::{DVec, Direction};
 use log::*;
 
 use super::MCI;
@@ -7,8 +7,16 @@ use super::constants::*;
 use super::err::*;
 use super::mci_data::MCIData;
 use super::regs::*;
+use crate::regs::Backoff;
 
-#[derive(Default)]
+/// A descriptor of the IDMAC in chained mode.
+///
+/// Each descriptor is padded to a cache line of its own, the IDMAC follows
+/// `desc_lo`/`desc_hi` to the next one so the spacing is free. Cleaning or
+/// invalidating one descriptor then never touches a neighbour the IDMAC
+/// may be writing back at the same time.
+#[repr(C, align(64))]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct FSdifIDmaDesc {
     pub attribute: u32,
     pub non1: u32,
@@ -20,22 +28,381 @@ pub struct FSdifIDmaDesc {
     pub desc_hi: u32,
 }
 
+/// Descriptor ring of the IDMAC.
+///
+/// The descriptors are chained once when the list is attached; a transfer
+/// only fills in buffer addresses, lengths and the OWN/FD/LD bits, and
+/// checks the IDMAC handed every descriptor back once it's over.
+///
+/// The ring lives in cached memory, every access to a descriptor cleans or
+/// invalidates its cache line. That is only safe because no two descriptors
+/// share a line, see [`FSdifIDmaDesc`].
 pub struct FSdifIDmaDescList {
-    pub first_desc: *mut FSdifIDmaDesc,
-    pub first_desc_dma: usize, // Physical address of the first descriptor
+    pub descs: Option<DVec<FSdifIDmaDesc>>, // Descriptors, mapped for the IDMAC
+    pub first_desc_dma: usize,              // Physical address of the first descriptor
     pub desc_num: u32,
     pub desc_trans_sz: u32, // Bytes transferred by a single descriptor
+    pub desc_in_use: u32,   // Descriptors handed to the IDMAC by the current transfer
+    pub chunk_descs: u32,   // Descriptors per chunk of the running stream, 0 if none
+}
+
+/* cache line size, the unit the cache is cleaned and invalidated in */
+const MCI_DMA_CACHE_LINE: usize = 64;
+
+/// A data buffer mapped for the IDMAC for the length of one transfer.
+///
+/// Mapping cleans the buffer from the cache so the device sees the data to
+/// write and no dirty line gets evicted over the data it reads; unmapping
+/// invalidates it after a read so the CPU sees what the device wrote.
+///
+/// Invalidating a line drops whatever else shares it, so a buffer that
+/// doesn't own whole cache lines bounces through an aligned one instead.
+pub(crate) struct MCIDmaBuf {
+    dvec: DVec<u32>,
+    len: usize,
+    direction: Direction,
+    bounced: Option<Vec<u32>>, // The caller's buffer while the data goes through `dvec`
+}
+
+impl MCIDmaBuf {
+    /// Map `buf` for the IDMAC, handing it back if the bounce buffer can't
+    /// be allocated.
+    pub(crate) fn map(mut buf: Vec<u32>, direction: Direction) -> Result<Self, Vec<u32>> {
+        let len = buf.len();
+        buf.shrink_to_fit();
+        let line_aligned = (buf.as_ptr() as usize).is_multiple_of(MCI_DMA_CACHE_LINE)
+            && (buf.capacity() * 4).is_multiple_of(MCI_DMA_CACHE_LINE);
+        if line_aligned {
+            /* the whole allocation is mapped, don't leave part of it uninitialized */
+            buf.resize(buf.capacity(), 0);
+            return Ok(MCIDmaBuf {
+                dvec: DVec::from_vec(buf, direction),
+                len,
+                direction,
+                bounced: None,
+            });
+        }
+
+        let words = (len * 4).next_multiple_of(MCI_DMA_CACHE_LINE) / 4;
+        let Some(mut dvec) = DVec::zeros(words, MCI_DMA_CACHE_LINE, direction) else {
+            error!("alloc DMA bounce buffer of {} words failed", words);
+            return Err(buf);
+        };
+        if direction != Direction::FromDevice {
+            dvec.copy_from_slice(&buf);
+        }
+        Ok(MCIDmaBuf {
+            dvec,
+            len,
+            direction,
+            bounced: Some(buf),
+        })
+    }
+
+    pub(crate) fn bus_addr(&self) -> usize {
+        self.dvec.bus_addr() as usize
+    }
+
+    pub(crate) fn unmap(self) -> Vec<u32> {
+        let Some(mut buf) = self.bounced else {
+            let mut buf = self.dvec.to_vec();
+            buf.truncate(self.len);
+            return buf;
+        };
+        if self.direction != Direction::ToDevice {
+            /* reading through the DVec invalidates it first */
+            let data: &[u32] = &self.dvec;
+            buf.copy_from_slice(&data[..self.len]);
+        }
+        buf
+    }
+}
+
+/// A piece of a scatter-gather DMA buffer.
+///
+/// The IDMAC needs `bus_addr` and `len` to be multiples of 4 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MCIDmaSegment {
+    /// Bus address of the segment
+    pub bus_addr: usize,
+    /// Length of the segment in bytes
+    pub len: u32,
+}
+
+impl MCIDmaSegment {
+    pub fn new(bus_addr: usize, len: u32) -> Self {
+        MCIDmaSegment { bus_addr, len }
+    }
+}
+
+/// The part of `segments` covering bytes `offset..offset + len` of the buffer they form.
+pub(crate) fn dma_segments_slice(
+    segments: &[MCIDmaSegment],
+    offset: usize,
+    len: usize,
+) -> Vec<MCIDmaSegment> {
+    let mut slice = Vec::new();
+    let mut start = 0usize;
+    let end = offset + len;
+    for seg in segments {
+        let seg_end = start + seg.len as usize;
+        let lo = start.max(offset);
+        let hi = seg_end.min(end);
+        if lo < hi {
+            slice.push(MCIDmaSegment::new(
+                seg.bus_addr + (lo - start),
+                (hi - lo) as u32,
+            ));
+        }
+        if seg_end >= end {
+            break;
+        }
+        start = seg_end;
+    }
+    slice
+}
+
+impl Default for FSdifIDmaDescList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FSdifIDmaDescList {
     pub fn new() -> Self {
         FSdifIDmaDescList {
-            first_desc: core::ptr::null_mut(),
+            descs: None,
             first_desc_dma: 0,
             desc_num: 0,
             desc_trans_sz: 0,
+            desc_in_use: 0,
+            chunk_descs: 0,
+        }
+    }
+
+    /// Take `descs` as the ring and chain its descriptors, the last back to the first.
+    ///
+    /// `descs` must start on a cache line, so each descriptor fills one.
+    pub(crate) fn attach(&mut self, mut descs: DVec<FSdifIDmaDesc>) -> MCIResult {
+        let first_desc_dma = descs.bus_addr() as usize;
+        if !first_desc_dma.is_multiple_of(MCI_DMA_CACHE_LINE) || descs.is_empty() {
+            // make sure every descriptor owns its cache line
+            error!("DMA descriptor 0x{:x} do not align!", first_desc_dma);
+            return Err(MCIError::DmaBufUnalign);
+        }
+
+        self.first_desc_dma = first_desc_dma;
+        self.desc_num = descs.len() as u32;
+        self.desc_trans_sz = FSDIF_IDMAC_MAX_BUF_SIZE;
+        self.desc_in_use = 0;
+        for i in 0..descs.len() {
+            let desc = self.template(i);
+            descs.set(i, desc);
+        }
+        self.descs = Some(descs);
+        Ok(())
+    }
+
+    /// Give the ring back, it's no longer usable by the controller.
+    pub(crate) fn detach(&mut self) -> Option<DVec<FSdifIDmaDesc>> {
+        self.first_desc_dma = 0;
+        self.desc_num = 0;
+        self.desc_in_use = 0;
+        self.chunk_descs = 0;
+        self.descs.take()
+    }
+
+    /// Descriptor `index` as linked in the ring, owned by the CPU with no buffer.
+    fn template(&self, index: usize) -> FSdifIDmaDesc {
+        let next = (index + 1) % self.desc_num as usize;
+        let next_desc_addr = self.first_desc_dma + next * core::mem::size_of::<FSdifIDmaDesc>();
+        let mut attribute = FSDIF_IDMAC_DES0_CH;
+        if next == 0 {
+            attribute |= FSDIF_IDMAC_DES0_ER;
+        }
+        // upper address words are zero on 32-bit
+        FSdifIDmaDesc {
+            attribute,
+            desc_lo: next_desc_addr as u32,
+            desc_hi: ((next_desc_addr as u64) >> 32) as u32,
+            ..Default::default()
         }
     }
+
+    /// Hand the descriptors covering `segments` to the IDMAC.
+    ///
+    /// Returns the number of descriptors used.
+    pub(crate) fn prepare(&mut self, segments: &[MCIDmaSegment]) -> MCIResult<u32> {
+        if let Some(seg) = segments
+            .iter()
+            .find(|seg| !seg.bus_addr.is_multiple_of(4) || !seg.len.is_multiple_of(4))
+        {
+            error!(
+                "DMA segment 0x{:x}+{} do not align to 4!",
+                seg.bus_addr, seg.len
+            );
+            return Err(MCIError::DmaBufUnalign);
+        }
+
+        let desc_trans_sz = self.desc_trans_sz;
+        let desc_num: u32 = segments
+            .iter()
+            .map(|seg| seg.len.div_ceil(desc_trans_sz))
+            .sum();
+        if desc_num > self.desc_num {
+            error!(
+                "Transfer descriptor are not enough! desc need: {}, desc available: {}",
+                desc_num, self.desc_num
+            );
+            return Err(MCIError::ShortBuf);
+        }
+
+        let chunks = segments.iter().flat_map(|seg| {
+            (0..seg.len)
+                .step_by(desc_trans_sz as usize)
+                .map(move |off| {
+                    (
+                        seg.bus_addr + off as usize,
+                        (seg.len - off).min(desc_trans_sz),
+                    )
+                })
+        });
+
+        for (i, (buf_addr, len)) in (0..desc_num).zip(chunks) {
+            let mut desc = self.template(i as usize);
+            desc.attribute |= FSDIF_IDMAC_DES0_OWN;
+            if i == 0 {
+                desc.attribute |= FSDIF_IDMAC_DES0_FD;
+            }
+            if i == desc_num - 1 {
+                desc.attribute |= FSDIF_IDMAC_DES0_LD;
+            }
+            desc.len = len;
+            desc.addr_lo = buf_addr as u32;
+            desc.addr_hi = ((buf_addr as u64) >> 32) as u32;
+            // setting it cleans it from the cache
+            self.descs
+                .as_mut()
+                .ok_or(MCIError::NotInit)?
+                .set(i as usize, desc);
+        }
+
+        self.desc_in_use = desc_num;
+        Ok(desc_num)
+    }
+
+    /// Check the IDMAC handed back the descriptors of the finished transfer.
+    pub(crate) fn reclaim(&mut self) -> MCIResult {
+        let desc_in_use = core::mem::take(&mut self.desc_in_use) as usize;
+        let descs = self.descs.as_ref().ok_or(MCIError::NotInit)?;
+
+        /* the IDMAC writes the descriptor back right after the data, allow it a moment */
+        let mut backoff = Backoff::new(FSDIF_DMA_DESC_RECLAIM_TIMEOUT);
+        for i in 0..desc_in_use {
+            while descs
+                .get(i)
+                .is_some_and(|desc| desc.attribute & FSDIF_IDMAC_DES0_OWN != 0)
+            {
+                if !backoff.snooze() {
+                    error!("DMA descriptor {} still owned by the IDMAC!", i);
+                    return Err(MCIError::DmaDescOwned);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lay out a closed ring over `chunks`, bus addresses of `chunk_len`
+    /// byte buffers, for an open-ended transfer.
+    ///
+    /// Only the first `armed` chunks are handed to the IDMAC, it suspends
+    /// on the others until they are armed. The IDMAC keeps running while
+    /// chunks are armed and checked, which is fine as every descriptor has
+    /// its own cache line.
+    pub(crate) fn stream_prepare(
+        &mut self,
+        chunks: &[usize],
+        chunk_len: u32,
+        armed: usize,
+    ) -> MCIResult {
+        let desc_trans_sz = self.desc_trans_sz;
+        let chunk_descs = chunk_len.div_ceil(desc_trans_sz);
+        let desc_num = chunk_descs * chunks.len() as u32;
+        if chunks.is_empty() || chunk_len == 0 || desc_num > self.desc_num {
+            error!(
+                "Stream descriptor are not enough! desc need: {}, desc available: {}",
+                desc_num, self.desc_num
+            );
+            return Err(MCIError::ShortBuf);
+        }
+
+        for (chunk, &buf_addr) in chunks.iter().enumerate() {
+            for n in 0..chunk_descs {
+                let i = chunk * chunk_descs as usize + n as usize;
+                let mut desc = self.template(i);
+                if i == desc_num as usize - 1 {
+                    /* the stream wraps around before the end of the list */
+                    desc.attribute |= FSDIF_IDMAC_DES0_ER;
+                    desc.desc_lo = self.first_desc_dma as u32;
+                    desc.desc_hi = ((self.first_desc_dma as u64) >> 32) as u32;
+                }
+                if i == 0 {
+                    desc.attribute |= FSDIF_IDMAC_DES0_FD;
+                }
+                if chunk < armed {
+                    desc.attribute |= FSDIF_IDMAC_DES0_OWN;
+                }
+                let off = n * desc_trans_sz;
+                let addr = buf_addr + off as usize;
+                desc.len = (chunk_len - off).min(desc_trans_sz);
+                desc.addr_lo = addr as u32;
+                desc.addr_hi = ((addr as u64) >> 32) as u32;
+                self.descs.as_mut().ok_or(MCIError::NotInit)?.set(i, desc);
+            }
+        }
+
+        self.chunk_descs = chunk_descs;
+        self.desc_in_use = desc_num;
+        Ok(())
+    }
+
+    /// Hand the descriptors of `chunk` back to the IDMAC.
+    ///
+    /// The chunk must be done, re-arming descriptors the IDMAC still owns
+    /// could race with its write-back.
+    pub(crate) fn chunk_arm(&mut self, chunk: usize) -> MCIResult {
+        let descs = self.descs.as_mut().ok_or(MCIError::NotInit)?;
+        let first = chunk * self.chunk_descs as usize;
+        for i in first..first + self.chunk_descs as usize {
+            let mut desc = descs.get(i).ok_or(MCIError::InvalidState)?;
+            if desc.attribute & FSDIF_IDMAC_DES0_OWN != 0 {
+                error!("DMA stream chunk {} is still owned by the IDMAC!", chunk);
+                return Err(MCIError::InvalidState);
+            }
+            desc.attribute = (desc.attribute | FSDIF_IDMAC_DES0_OWN) & !FSDIF_IDMAC_DES0_FD;
+            descs.set(i, desc);
+        }
+        Ok(())
+    }
+
+    /// Whether the IDMAC handed back every descriptor of `chunk`.
+    pub(crate) fn chunk_done(&self, chunk: usize) -> MCIResult<bool> {
+        let descs = self.descs.as_ref().ok_or(MCIError::NotInit)?;
+        let first = chunk * self.chunk_descs as usize;
+        for i in first..first + self.chunk_descs as usize {
+            let desc = descs.get(i).ok_or(MCIError::InvalidState)?;
+            if desc.attribute & FSDIF_IDMAC_DES0_OWN != 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Forget the stream, the descriptors are laid out again by the next transfer.
+    pub(crate) fn stream_end(&mut self) {
+        self.chunk_descs = 0;
+        self.desc_in_use = 0;
+    }
 }
 
 /* DMA-related functions */
@@ -48,154 +415,80 @@ impl MCI {
 
     pub fn dump_dma_descriptor(&self, desc_in_use: u32) {
         trace!("{} dma desc in use!", desc_in_use);
-        if !self.desc_list.first_desc.is_null() {
-            for i in 0..desc_in_use {
-                unsafe {
-                    let cur_desc = &*self.desc_list.first_desc.add(i as usize);
-                    trace!("descriptor no {} @{:p}", i, cur_desc);
-                    trace!("\tattribute: 0x{:x}", (*cur_desc).attribute);
-                    trace!("\tnon1: 0x{:x}", (*cur_desc).non1);
-                    trace!("\tlen: 0x{:x}", (*cur_desc).len);
-                    trace!("\tnon2: 0x{:x}", (*cur_desc).non2);
-                    trace!("\taddr_lo: 0x{:x}", (*cur_desc).addr_lo);
-                    trace!("\taddr_hi: 0x{:x}", (*cur_desc).addr_hi);
-                    trace!("\tdesc_lo: 0x{:x}", (*cur_desc).desc_lo);
-                    trace!("\tdesc_hi: 0x{:x}", (*cur_desc).desc_hi);
-                }
+        if let Some(descs) = &self.desc_list.descs {
+            for i in 0..desc_in_use as usize {
+                let Some(cur_desc) = descs.get(i) else {
+                    break;
+                };
+                trace!(
+                    "descriptor no {} @0x{:x}",
+                    i,
+                    self.desc_list.first_desc_dma + i * core::mem::size_of::<FSdifIDmaDesc>()
+                );
+                trace!("\tattribute: 0x{:x}", cur_desc.attribute);
+                trace!("\tnon1: 0x{:x}", cur_desc.non1);
+                trace!("\tlen: 0x{:x}", cur_desc.len);
+                trace!("\tnon2: 0x{:x}", cur_desc.non2);
+                trace!("\taddr_lo: 0x{:x}", cur_desc.addr_lo);
+                trace!("\taddr_hi: 0x{:x}", cur_desc.addr_hi);
+                trace!("\tdesc_lo: 0x{:x}", cur_desc.desc_lo);
+                trace!("\tdesc_hi: 0x{:x}", cur_desc.desc_hi);
             }
         }
         trace!("dump ok");
     }
 
     /// setup DMA descriptor list before do transcation
+    ///
+    /// The descriptors cover the scatter list of `data`, or its single
+    /// buffer if it has none, one descriptor per `desc_trans_sz` bytes of a segment.
     pub(crate) fn setup_dma_descriptor(&mut self, data: &MCIData) -> MCIResult {
-        let desc_list = &self.desc_list;
-        // Number of blocks that can be transferred by one descriptor
-        let desc_blocks = desc_list.desc_trans_sz / data.blksz();
-        let mut remain_blocks = data.blkcnt();
-        let mut buf_addr = data.buf_dma();
-        let mut trans_blocks: u32;
-        let mut is_first;
-        let mut is_last;
+        if data.segments().is_empty() && data.buf_dma() == 0 {
+            error!("no buffer mapped for the DMA transfer");
+            return Err(MCIError::NotInit);
+        }
+        let single = [MCIDmaSegment::new(data.buf_dma(), data.datalen())];
+        let segments = if data.segments().is_empty() {
+            &single[..]
+        } else {
+            data.segments()
+        };
 
         trace!(
-            "Setup DMA descriptor for data transfer, buf: 0x{:x}, blkcnt: {}, blksz: {}",
-            data.buf_dma(),
+            "Setup DMA descriptor for data transfer, {} segment(s), blkcnt: {}, blksz: {}",
+            segments.len(),
             data.blkcnt(),
             data.blksz()
         );
 
-        let mut desc_num = 1u32;
         let data_len = data.blkcnt() * data.blksz();
-        // Calculate how many descriptors are needed for transfer
-        if data_len > desc_list.desc_trans_sz {
-            desc_num = data_len / desc_list.desc_trans_sz;
-            desc_num += if data_len % desc_list.desc_trans_sz == 0 {
-                0
-            } else {
-                1
-            };
+        let mut seg_len = 0u32;
+        for seg in segments {
+            // the IDMAC moves whole words
+            if !seg.bus_addr.is_multiple_of(4) || !seg.len.is_multiple_of(4) || seg.len == 0 {
+                error!(
+                    "DMA segment 0x{:x} len {} do not align to 4!",
+                    seg.bus_addr, seg.len
+                );
+                return Err(MCIError::DmaBufUnalign);
+            }
+            seg_len += seg.len;
         }
 
-        if desc_num > desc_list.desc_num {
+        if seg_len != data_len {
             error!(
-                "Transfer descriptor are not enough! desc need: {}, desc available: {}",
-                desc_num, desc_list.desc_num
+                "DMA segments cover {} bytes, transfer needs {}!",
+                seg_len, data_len
             );
             return Err(MCIError::ShortBuf);
         }
 
+        let desc_num = self.desc_list.prepare(segments)?;
         debug!(
             "DMA transfer 0x{:x} use {} desc, total {} available",
-            data.buf_dma(),
-            desc_num,
-            desc_list.desc_num
+            segments[0].bus_addr, desc_num, self.desc_list.desc_num
         );
 
-        // setup DMA descriptor list, so that we just need to update buffer address in each transcation
-        let total_size = desc_list.desc_num as usize * core::mem::size_of::<FSdifIDmaDesc>();
-        unsafe {
-            core::ptr::write_bytes(desc_list.first_desc as *mut u8, 0, total_size);
-        }
-
-        for i in 0..desc_num {
-            trans_blocks = if remain_blocks <= desc_blocks {
-                remain_blocks
-            } else {
-                desc_blocks
-            };
-            unsafe {
-                let cur_desc = self.desc_list.first_desc.add(i as usize);
-                let mut next_desc_addr = desc_list.first_desc_dma
-                    + (i + 1) as usize * core::mem::size_of::<FSdifIDmaDesc>();
-
-                is_first = i == 0;
-                is_last = desc_num - 1 == i;
-
-                // set properity of descriptor entry
-                (*cur_desc).attribute = FSDIF_IDMAC_DES0_CH | FSDIF_IDMAC_DES0_OWN;
-                if is_first {
-                    (*cur_desc).attribute |= FSDIF_IDMAC_DES0_FD;
-                }
-                if is_last {
-                    (*cur_desc).attribute |= FSDIF_IDMAC_DES0_LD | FSDIF_IDMAC_DES0_ER;
-                }
-
-                // set data length in transfer
-                (*cur_desc).non1 = 0u32;
-                (*cur_desc).len = trans_blocks * data.blksz();
-
-                // set data buffer for transfer
-                if buf_addr % data.blksz() as usize != 0 {
-                    error!(
-                        "Data buffer 0x{:x} do not align to {}!",
-                        buf_addr,
-                        data.blksz()
-                    );
-                    return Err(MCIError::DmaBufUnalign);
-                }
-
-                if cfg!(target_arch = "aarch64") {
-                    (*cur_desc).addr_hi = ((buf_addr >> 32) & 0xFFFF_FFFF) as u32;
-                    (*cur_desc).addr_lo = (buf_addr & 0xFFFF_FFFF) as u32;
-                } else {
-                    (*cur_desc).addr_hi = 0;
-                    (*cur_desc).addr_lo = (buf_addr & 0xFFFF_FFFF) as u32;
-                }
-
-                // set address of next descriptor entry, NULL for last entry
-                next_desc_addr = if is_last { 0 } else { next_desc_addr };
-                if next_desc_addr as usize % core::mem::size_of::<FSdifIDmaDesc>() != 0 {
-                    // make sure descriptor aligned and not cross page boundary
-                    error!("DMA descriptor 0x{:x} do not align!", next_desc_addr);
-                    return Err(MCIError::DmaBufUnalign);
-                }
-
-                if cfg!(target_arch = "aarch64") {
-                    (*cur_desc).desc_hi = ((next_desc_addr >> 32) & 0xFFFF_FFFF) as u32;
-                    (*cur_desc).desc_lo = (next_desc_addr & 0xFFFF_FFFF) as u32;
-                } else {
-                    (*cur_desc).desc_hi = 0;
-                    (*cur_desc).desc_lo = (next_desc_addr & 0xFFFF_FFFF) as u32;
-                }
-
-                buf_addr += (*cur_desc).len as usize;
-                remain_blocks -= trans_blocks;
-            }
-        }
-
-        // TODO: Not very elegant, consider modifying later
-        let desc_vec = unsafe {
-            core::mem::ManuallyDrop::new(Vec::from_raw_parts(
-                desc_list.first_desc,
-                desc_num as usize,
-                desc_num as usize,
-            ))
-        };
-
-        let _ = DSlice::from(&desc_vec[..]);
-        // unsafe { dsb(); }
-
         self.dump_dma_descriptor(desc_num);
         trace!("set dma desc ok");
 
@@ -211,12 +504,12 @@ impl MCI {
         );
         self.interrupt_mask_set(MCIIntrType::DmaIntr, MCIDMACIntEn::INTS_MASK.bits(), true);
 
-        self.setup_dma_descriptor(&data)?;
+        self.setup_dma_descriptor(data)?;
 
         let data_len = data.blkcnt() * data.blksz();
         debug!(
-            "Descriptor@{:p}, trans bytes: {}, block size: {}",
-            self.desc_list.first_desc,
+            "Descriptor@0x{:x}, trans bytes: {}, block size: {}",
+            self.desc_list.first_desc_dma,
             data_len,
             data.blksz()
         );
@@ -41,9 +41,9 @@ impl MCI {
         let reg = self.config.reg();
         reg.write_reg(MCIClkSrc::empty());
         reg.write_reg(uhs_reg);
-        reg.retry_for(
+        reg.poll_until(
             |reg: MCIClkSts| reg.contains(MCIClkSts::READY),
-            Some(RETRIES_TIMEOUT),
+            self.config.cmd_timeout(),
         )?;
         Ok(())
     }
@@ -93,9 +93,9 @@ impl MCI {
         let reg = self.config.reg();
 
         reg.modify_reg(|reg| reset_bits | reg);
-        if let Err(e) = reg.retry_for(
+        if let Err(e) = reg.poll_until(
             |reg: MCICtrl| !reg.contains(reset_bits),
-            Some(RETRIES_TIMEOUT),
+            self.config.cmd_timeout(),
         ) {
             error!("Reset failed, bits = 0x{:x}", reset_bits);
             return Err(e);
@@ -110,9 +110,9 @@ impl MCI {
 
         /* for fifo reset, need to check if fifo empty */
         if reset_bits.contains(MCICtrl::FIFO_RESET)
-            && let Err(e) = reg.retry_for(
+            && let Err(e) = reg.poll_until(
                 |reg: MCIStatus| reg.contains(MCIStatus::FIFO_EMPTY),
-                Some(RETRIES_TIMEOUT),
+                self.config.cmd_timeout(),
             )
         {
             error!("Fifo not empty!");
@@ -182,6 +182,36 @@ impl MCI {
         reg.write_reg(self.dma_status_get());
     }
 
+    /// Whether the current command is CMD11, whose voltage switch interrupt
+    /// the controller raises on the HTO bit.
+    pub(crate) fn volt_switch_pending(&self) -> bool {
+        self.cur_cmd
+            .as_ref()
+            .is_some_and(|cmd| cmd.flag().contains(MCICmdFlag::SWITCH_VOLTAGE))
+    }
+
+    /// The raw interrupt bits that fail the current command.
+    pub(crate) fn ints_err_mask(&self) -> MCIRawInts {
+        if self.volt_switch_pending() {
+            MCIRawInts::INTS_ERR_MASK - MCIRawInts::HTO_BIT
+        } else {
+            MCIRawInts::INTS_ERR_MASK
+        }
+    }
+
+    /// Decode the error bits of the raw interrupt status and, in DMA mode,
+    /// of the internal DMA status. Returns `None` if no error is pending.
+    pub(crate) fn transfer_error_get(&self) -> Option<MCIError> {
+        let raw_ints = self.raw_status_get() & self.ints_err_mask();
+        if let Some(err) = MCIError::from_raw_ints(raw_ints.bits()) {
+            return Some(err);
+        }
+        if self.config.trans_mode() == MCITransMode::DMA {
+            return MCIError::from_dma_status(self.dma_status_get().bits());
+        }
+        None
+    }
+
     pub(crate) fn check_if_card_exist(&self) -> bool {
         let reg = self.config.reg();
         !reg.read_reg::<MCICardDetect>()
@@ -1,8 +1,109 @@
+use core::ptr::NonNull;
+#[cfg(feature = "pio")]
+use core::time::Duration;
+
+use alloc::sync::Arc;
+
 use super::MCI;
 use super::constants::*;
+#[cfg(feature = "pio")]
+use super::err::*;
 use super::regs::*;
+use crate::osa::OsaEvent;
+use crate::regs::Backoff;
+
+/* everything but card detect and the SDIO card interrupt, which are handled apart */
+const TRANSFER_INTS: u32 =
+    MCIIntMask::ALL_BITS.bits() & !(MCIIntMask::CD_BIT.bits() | MCIIntMask::SDIO_BIT.bits());
 
 impl MCI {
+    /// Acknowledge a pending card-detect interrupt of the controller at `addr`.
+    ///
+    /// Only touches the card-detect status bit, so it can run in the interrupt
+    /// handler while a transfer is in progress. Returns whether the interrupt
+    /// was pending.
+    pub fn card_detect_irq_ack(addr: NonNull<u8>) -> bool {
+        let reg = MCIReg::new(addr);
+        if !reg
+            .read_reg::<MCIMaskedInts>()
+            .contains(MCIMaskedInts::CD_BIT)
+        {
+            return false;
+        }
+        /* write one to clear */
+        reg.write_reg(MCIRawInts::CD_BIT);
+        true
+    }
+
+    /// Acknowledge a pending transfer interrupt of the controller at `addr`:
+    /// command done, data transfer over, a PIO FIFO request (RXDR/TXDR), an
+    /// error or an IDMAC event.
+    ///
+    /// The pending sources are masked rather than cleared, the transfer
+    /// waiting for them reads their status and unmasks them again. Notify the
+    /// event set with [`irq_event_set`](Self::irq_event_set) when it returns
+    /// `true`. Doesn't touch card detect, see [`card_detect_irq_ack`](Self::card_detect_irq_ack).
+    pub fn transfer_irq_ack(addr: NonNull<u8>) -> bool {
+        let reg = MCIReg::new(addr);
+        let pending = reg.read_reg::<MCIMaskedInts>().bits() & TRANSFER_INTS;
+        let dma_pending = reg.read_reg::<MCIDMACStatus>().bits()
+            & reg.read_reg::<MCIDMACIntEn>().bits()
+            & MCIDMACIntEn::ALL_BITS.bits();
+        if pending != 0 {
+            reg.clear_reg(MCIIntMask::from_bits_truncate(pending));
+        }
+        if dma_pending != 0 {
+            /* the transfer's own wait reads the IDMAC status, DTO tells it's over */
+            reg.write_reg(MCIDMACIntEn::empty());
+        }
+        pending != 0 || dma_pending != 0
+    }
+
+    /// Wait for transfers on `event` instead of polling, `None` polls again.
+    ///
+    /// The interrupt handler calls [`transfer_irq_ack`](Self::transfer_irq_ack)
+    /// and notifies `event`.
+    pub fn irq_event_set(&mut self, event: Option<Arc<OsaEvent>>) {
+        self.irq_event = event;
+    }
+
+    /// Wait before the next check of the raw interrupt status for `bits`.
+    ///
+    /// In interrupt mode the `bits` not raised yet are unmasked and the wait
+    /// sleeps on the event until the interrupt handler notifies it, otherwise
+    /// it backs off.
+    /// Returns `false` once the deadline of `backoff` has passed.
+    pub(crate) fn ints_wait(&self, backoff: &mut Backoff, bits: u32) -> bool {
+        let Some(event) = self.irq_event.as_ref() else {
+            return backoff.snooze();
+        };
+        let remaining = backoff.remaining();
+        if remaining.is_zero() {
+            return false;
+        }
+        /*
+         * a raised status stays until the transfer acks it, unmasking it again
+         * would interrupt right away; one raised after the read isn't lost
+         */
+        let pending = bits & !self.raw_status_get().bits();
+        self.interrupt_mask_set(MCIIntrType::GeneralIntr, pending, true);
+        event.wait(remaining);
+        true
+    }
+
+    /// Wait until any of `bits` is raised in the raw interrupt status.
+    #[cfg(feature = "pio")]
+    pub(crate) fn raw_ints_wait(&self, bits: u32, timeout: Duration) -> MCIResult {
+        let reg = self.config.reg();
+        let mut backoff = Backoff::new(timeout);
+        while reg.read_reg::<MCIRawInts>().bits() & bits == 0 {
+            if !self.ints_wait(&mut backoff, bits) {
+                return Err(MCIError::Timeout);
+            }
+        }
+        Ok(())
+    }
+
     /* Get SDIF controller interrupt mask */
     pub fn interrupt_mask_get(&self, tp: MCIIntrType) -> u32 {
         let reg = self.config.reg();
@@ -1,50 +1,159 @@
 use super::MCI;
 use super::constants::*;
 use super::err::*;
+use super::mci_cmddata::MCICmdData;
 use super::mci_data::MCIData;
 use super::regs::*;
+use crate::regs::Backoff;
 use log::*;
 
+/* words the FIFO holds */
+const MCI_FIFO_DEPTH_WORDS: usize = (MCI_MAX_FIFO_CNT / 4) as usize;
+
 impl MCI {
-    pub(crate) fn pio_write_data(&self, data: &MCIData) -> MCIResult {
+    /// Number of words currently in the FIFO.
+    fn fifo_count(&self) -> usize {
+        get_reg32_bits!(self.status_get().bits(), 29, 17) as usize
+    }
+
+    /// Write the next words of `data` to the FIFO, as many as fit.
+    ///
+    /// Returns whether any word was written.
+    pub(crate) fn pio_fifo_fill(&mut self, data: &MCIData) -> MCIResult<bool> {
         let reg = self.config.reg();
-        let wr_times: usize = (data.datalen() / 4) as usize; /* u8 --> u32 */
-        let buf = if let Some(buf) = data.buf() {
-            buf
-        } else {
-            return Err(MCIError::NotInit);
-        };
+        let total = (data.datalen() / 4) as usize; /* u8 --> u32 */
+        let buf = data.buf().ok_or(MCIError::NotInit)?;
 
-        /* write fifo data */
-        reg.write_reg(MCICmd::DAT_WRITE);
-        for &val in buf.iter().take(wr_times) {
+        let room = MCI_FIFO_DEPTH_WORDS.saturating_sub(self.fifo_count());
+        let end = total.min(self.pio_offset + room).min(buf.len());
+        for &val in &buf[self.pio_offset..end] {
             reg.write_reg(MCIDataReg::from_bits_truncate(val));
         }
-        Ok(())
+        let written = end - self.pio_offset;
+        self.pio_offset = end;
+        Ok(written != 0)
     }
 
-    pub(crate) fn pio_read_data(&self, data: &mut MCIData) -> MCIResult {
+    /// Read the words waiting in the FIFO into `data`.
+    ///
+    /// Returns whether any word was read.
+    pub(crate) fn pio_fifo_drain(&mut self, data: &mut MCIData) -> MCIResult<bool> {
+        let total = (data.datalen() / 4) as usize; /* u8 --> u32 */
+        let count = self.fifo_count().min(total.saturating_sub(self.pio_offset));
         let reg = self.config.reg();
-        let datalen = data.datalen();
-        let rd_times = (datalen / 4) as usize; /* u8 --> u32 */
-        let buf = if let Some(buf) = data.buf_mut() {
-            buf
+        let buf = data.buf_mut().ok_or(MCIError::NotInit)?;
+
+        for _ in 0..count {
+            buf.push(reg.read_reg::<MCIDataReg>().bits());
+        }
+        self.pio_offset += count;
+        Ok(count != 0)
+    }
+
+    /// Service the FIFO for the watermark status in `raw_ints`.
+    ///
+    /// Drains the FIFO above the RX watermark and refills it below the TX
+    /// watermark, acking the watermark status. Called from the data stream
+    /// loop each time it wakes up, see [`pio_fifo_irq_service`](Self::pio_fifo_irq_service)
+    /// to call it from the interrupt handler.
+    ///
+    /// Returns whether data moved.
+    pub(crate) fn pio_fifo_service(
+        &mut self,
+        cmd_data: &mut MCICmdData,
+        raw_ints: MCIRawInts,
+    ) -> MCIResult<bool> {
+        let read = cmd_data.flag().contains(MCICmdFlag::READ_DATA);
+        let reg = self.config.reg().clone();
+        let data = cmd_data.get_mut_data().ok_or(MCIError::NotInit)?;
+
+        if read && raw_ints.intersects(MCIRawInts::RXDR_BIT | MCIRawInts::DTO_BIT) {
+            /* ack before draining, the status is raised again while above the watermark */
+            reg.write_reg(MCIRawInts::RXDR_BIT);
+            return self.pio_fifo_drain(data);
+        }
+        if !read && raw_ints.contains(MCIRawInts::TXDR_BIT) {
+            reg.write_reg(MCIRawInts::TXDR_BIT);
+            return self.pio_fifo_fill(data);
+        }
+        Ok(false)
+    }
+
+    /// Service the FIFO of the PIO transfer in `cmd_data` on its RXDR/TXDR
+    /// interrupt, for a transfer started with [`pio_transfer`](Self::pio_transfer)
+    /// and not waited for with [`poll_wait_pio_end`](Self::poll_wait_pio_end).
+    ///
+    /// In interrupt mode `poll_wait_pio_end` services the FIFO itself, woken
+    /// by [`transfer_irq_ack`](Self::transfer_irq_ack).
+    ///
+    /// Returns whether data moved.
+    pub fn pio_fifo_irq_service(&mut self, cmd_data: &mut MCICmdData) -> MCIResult<bool> {
+        let raw_ints = self.raw_status_get();
+        self.pio_fifo_service(cmd_data, raw_ints)
+    }
+
+    /// Wait for the CMD12 the controller sends by itself after the data, if
+    /// the command asked for it.
+    pub(crate) fn auto_stop_wait(&self, cmd_data: &mut MCICmdData) -> MCIResult {
+        if !cmd_data.flag().contains(MCICmdFlag::NEED_AUTO_STOP) {
+            return Ok(());
+        }
+        let wait_mask = (MCIRawInts::ACD_BIT | MCIRawInts::INTS_ERR_MASK).bits();
+        if let Err(err) = self.raw_ints_wait(wait_mask, self.config.cmd_timeout()) {
+            let raw_ints = self.raw_status_get().bits();
+            error!("wait auto stop timeout, raw ints: 0x{:x}", raw_ints);
+            cmd_data.raw_ints_set(raw_ints);
+            return Err(err);
+        }
+        self.pio_error_check(cmd_data)
+    }
+
+    /// Stream the data phase of a PIO transfer through the FIFO until it is over.
+    ///
+    /// The transfer may be longer than the FIFO, it's moved a watermark at a time.
+    /// In interrupt mode it sleeps until RXDR/TXDR, DTO or an error.
+    pub(crate) fn pio_data_stream(&mut self, cmd_data: &mut MCICmdData) -> MCIResult {
+        let total = cmd_data
+            .get_data()
+            .map(|data| (data.datalen() / 4) as usize)
+            .ok_or(MCIError::NotInit)?;
+        let reg = self.config.reg().clone();
+        let mut backoff = Backoff::new(self.config.data_timeout());
+        let fifo_request = if cmd_data.flag().contains(MCICmdFlag::READ_DATA) {
+            MCIRawInts::RXDR_BIT
         } else {
-            return Err(MCIError::NotInit);
+            MCIRawInts::TXDR_BIT
         };
 
-        buf.clear();
+        loop {
+            let raw_ints = reg.read_reg::<MCIRawInts>();
+            if raw_ints.intersects(MCIRawInts::INTS_ERR_MASK) {
+                self.pio_error_check(cmd_data)?;
+            }
 
-        if datalen > MCI_MAX_FIFO_CNT {
-            error!(
-                "Fifo do not support writing more than 0x{:x}.",
-                MCI_MAX_FIFO_CNT
-            );
-            return Err(MCIError::NotSupport);
-        }
-        for _i in 0..rd_times {
-            buf.push(reg.read_reg::<MCIDataReg>().bits());
+            let over = raw_ints.contains(MCIRawInts::DTO_BIT);
+            let moved = self.pio_fifo_service(cmd_data, raw_ints)?;
+            if over && self.pio_offset >= total {
+                return Ok(());
+            }
+
+            /* TXDR stays raised once all is written, then only DTO is worth waking up for */
+            let mut wake = (MCIRawInts::DTO_BIT | MCIRawInts::INTS_ERR_MASK).bits();
+            if self.pio_offset < total {
+                wake |= fifo_request.bits();
+            }
+            if moved {
+                backoff.progress();
+            } else if !self.ints_wait(&mut backoff, wake) {
+                let raw_ints = self.raw_status_get().bits();
+                error!(
+                    "wait PIO data timeout, {} of {} words moved, raw ints: 0x{:x}",
+                    self.pio_offset, total, raw_ints
+                );
+                cmd_data.raw_ints_set(raw_ints);
+                self.raw_status_clear();
+                return Err(MCIError::Timeout);
+            }
         }
-        Ok(())
     }
 }
@@ -0,0 +1,521 @@
+//! # Register Snapshot
+//!
+//! Capture of all controller registers at one point in time, decoded field
+//! by field for logs and comparable against another capture.
+
+use core::fmt;
+
+use super::constants::*;
+use super::regs::MCIReg;
+
+/* registers in capture order, by name and offset */
+const SNAPSHOT_REGS: [(&str, u32); 43] = [
+    ("cntrl", FSDIF_CNTRL_OFFSET),
+    ("pwren", FSDIF_PWREN_OFFSET),
+    ("clkdiv", FSDIF_CLKDIV_OFFSET),
+    ("clkena", FSDIF_CLKENA_OFFSET),
+    ("tmout", FSDIF_TMOUT_OFFSET),
+    ("ctype", FSDIF_CTYPE_OFFSET),
+    ("blksiz", FSDIF_BLK_SIZ_OFFSET),
+    ("bytcnt", FSDIF_BYT_CNT_OFFSET),
+    ("intmask", FSDIF_INT_MASK_OFFSET),
+    ("cmdarg", FSDIF_CMD_ARG_OFFSET),
+    ("cmd", FSDIF_CMD_OFFSET),
+    ("resp0", FSDIF_RESP0_OFFSET),
+    ("resp1", FSDIF_RESP1_OFFSET),
+    ("resp2", FSDIF_RESP2_OFFSET),
+    ("resp3", FSDIF_RESP3_OFFSET),
+    ("mintsts", FSDIF_MASKED_INTS_OFFSET),
+    ("rintsts", FSDIF_RAW_INTS_OFFSET),
+    ("status", FSDIF_STATUS_OFFSET),
+    ("fifoth", FSDIF_FIFOTH_OFFSET),
+    ("cdetect", FSDIF_CARD_DETECT_OFFSET),
+    ("wrtprt", FSDIF_CARD_WRTPRT_OFFSET),
+    ("cksts", FSDIF_CKSTS_OFFSET),
+    ("tcbcnt", FSDIF_TRAN_CARD_CNT_OFFSET),
+    ("tbbcnt", FSDIF_TRAN_FIFO_CNT_OFFSET),
+    ("debnce", FSDIF_DEBNCE_OFFSET),
+    ("uid", FSDIF_UID_OFFSET),
+    ("vid", FSDIF_VID_OFFSET),
+    ("hwconf", FSDIF_HWCONF_OFFSET),
+    ("uhsreg", FSDIF_UHS_REG_OFFSET),
+    ("cardreset", FSDIF_CARD_RESET_OFFSET),
+    ("busmode", FSDIF_BUS_MODE_OFFSET),
+    ("dbaddrl", FSDIF_DESC_LIST_ADDRL_OFFSET),
+    ("dbaddrh", FSDIF_DESC_LIST_ADDRH_OFFSET),
+    ("idsts", FSDIF_DMAC_STATUS_OFFSET),
+    ("idinten", FSDIF_DMAC_INT_EN_OFFSET),
+    ("dscaddrl", FSDIF_CUR_DESC_ADDRL_OFFSET),
+    ("dscaddrh", FSDIF_CUR_DESC_ADDRH_OFFSET),
+    ("bufaddrl", FSDIF_CUR_BUF_ADDRL_OFFSET),
+    ("bufaddrh", FSDIF_CUR_BUF_ADDRH_OFFSET),
+    ("cardthrctl", FSDIF_CARD_THRCTL_OFFSET),
+    ("clksrc", FSDIF_CLK_SRC_OFFSET),
+    ("emmcddr", FSDIF_EMMC_DDR_REG_OFFSET),
+    ("enableshift", FSDIF_ENABLE_SHIFT_OFFSET),
+];
+
+const SNAPSHOT_REG_COUNT: usize = SNAPSHOT_REGS.len();
+
+/* serialized layout: version, register count, then each register little endian */
+const SNAPSHOT_FORMAT_VERSION: u8 = 1;
+const SNAPSHOT_HEADER_LEN: usize = 2;
+
+const INT_NAMES: [&str; 17] = [
+    "CD", "RE", "CMD", "DTO", "TXDR", "RXDR", "RCRC", "DCRC", "RTO", "DRTO", "HTO", "FRUN", "HLE",
+    "SBE", "ACD", "EBE", "SDIO",
+];
+const CTRL_NAMES: [&str; 26] = [
+    "CONTROLLER_RESET",
+    "FIFO_RESET",
+    "DMA_RESET",
+    "",
+    "INT_ENABLE",
+    "DMA_ENABLE",
+    "READ_WAIT",
+    "SEND_IRQ_RESPONSE",
+    "ABORT_READ_DATA",
+    "SEND_CCSD",
+    "SEND_AUTO_STOP_CCSD",
+    "ENDIAN",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "ENABLE_OD_PULLUP",
+    "USE_INTERNAL_DMAC",
+];
+const IDSTS_NAMES: [&str; 10] = ["TI", "RI", "FBE", "", "DU", "CES", "", "", "NIS", "AIS"];
+const CMD_FSM_STATES: [&str; 16] = [
+    "idle",
+    "send init sequence",
+    "tx cmd start bit",
+    "tx cmd tx bit",
+    "tx cmd index + arg",
+    "tx cmd crc7",
+    "tx cmd end bit",
+    "rx resp start bit",
+    "rx resp irq response",
+    "rx resp tx bit",
+    "rx resp cmd idx",
+    "rx resp data",
+    "rx resp crc7",
+    "rx resp end bit",
+    "cmd path wait NCC",
+    "wait cmd-to-resp turnaround",
+];
+const DMAC_FSM_STATES: [&str; 9] = [
+    "idle",
+    "suspend",
+    "desc read",
+    "desc check",
+    "read request wait",
+    "write request wait",
+    "read",
+    "write",
+    "desc close",
+];
+
+/* bit names of a register value, joined with '|' */
+struct BitNames<'a>(u32, &'a [&'a str]);
+
+impl fmt::Display for BitNames<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (bit, name) in self.1.iter().enumerate() {
+            if name.is_empty() || self.0 & (1 << bit) == 0 {
+                continue;
+            }
+            if !first {
+                f.write_str("|")?;
+            }
+            f.write_str(name)?;
+            first = false;
+        }
+        if first {
+            f.write_str("-")?;
+        }
+        Ok(())
+    }
+}
+
+/// Values of all controller registers captured at one point in time.
+///
+/// Obtained with [`MCI::register_snapshot`](super::MCI::register_snapshot).
+/// `Display` prints every register with its fields decoded, `Debug` the raw
+/// values.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RegisterSnapshot {
+    regs: [u32; SNAPSHOT_REG_COUNT],
+}
+
+/// A register whose value differs between two [`RegisterSnapshot`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    /// Register name
+    pub name: &'static str,
+    /// Register offset
+    pub offset: u32,
+    /// Value in the earlier snapshot
+    pub old: u32,
+    /// Value in the later snapshot
+    pub new: u32,
+}
+
+impl fmt::Display for RegisterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}@0x{:x}: 0x{:08x} -> 0x{:08x} (changed 0x{:08x})",
+            self.name,
+            self.offset,
+            self.old,
+            self.new,
+            self.old ^ self.new
+        )
+    }
+}
+
+impl RegisterSnapshot {
+    /// Length of the serialized form in bytes.
+    pub const ENCODED_LEN: usize = SNAPSHOT_HEADER_LEN + SNAPSHOT_REG_COUNT * 4;
+
+    pub(crate) fn capture(reg: &MCIReg) -> Self {
+        let mut regs = [0; SNAPSHOT_REG_COUNT];
+        for (value, (_, offset)) in regs.iter_mut().zip(SNAPSHOT_REGS.iter()) {
+            *value = reg.read_32(*offset);
+        }
+        Self { regs }
+    }
+
+    /// Raw value of the register at `offset`, `None` if it isn't captured.
+    pub fn get(&self, offset: u32) -> Option<u32> {
+        SNAPSHOT_REGS
+            .iter()
+            .position(|(_, o)| *o == offset)
+            .map(|i| self.regs[i])
+    }
+
+    fn reg(&self, offset: u32) -> u32 {
+        self.get(offset).unwrap_or(0)
+    }
+
+    /// Names, offsets and raw values of all captured registers.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u32, u32)> + '_ {
+        SNAPSHOT_REGS
+            .iter()
+            .zip(self.regs.iter())
+            .map(|((name, offset), value)| (*name, *offset, *value))
+    }
+
+    /// Registers that differ from `earlier`.
+    pub fn diff<'a>(&'a self, earlier: &'a Self) -> impl Iterator<Item = RegisterChange> + 'a {
+        self.iter()
+            .zip(earlier.regs.iter())
+            .filter(|((_, _, new), old)| new != *old)
+            .map(|((name, offset, new), old)| RegisterChange {
+                name,
+                offset,
+                old: *old,
+                new,
+            })
+    }
+
+    /// Serialize to [`ENCODED_LEN`](Self::ENCODED_LEN) bytes for crash logs.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        bytes[0] = SNAPSHOT_FORMAT_VERSION;
+        bytes[1] = SNAPSHOT_REG_COUNT as u8;
+        for (chunk, value) in bytes[SNAPSHOT_HEADER_LEN..]
+            .chunks_exact_mut(4)
+            .zip(self.regs.iter())
+        {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decode bytes written by [`to_bytes`](Self::to_bytes).
+    ///
+    /// Returns `None` if the length, version or register count don't match.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::ENCODED_LEN
+            || bytes[0] != SNAPSHOT_FORMAT_VERSION
+            || bytes[1] as usize != SNAPSHOT_REG_COUNT
+        {
+            return None;
+        }
+        let mut regs = [0; SNAPSHOT_REG_COUNT];
+        for (value, chunk) in regs
+            .iter_mut()
+            .zip(bytes[SNAPSHOT_HEADER_LEN..].chunks_exact(4))
+        {
+            *value = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Some(Self { regs })
+    }
+
+    /// Clock divider, the card clock is the source clock / (2 * divider), 0 bypasses it.
+    pub fn clk_divider(&self) -> u32 {
+        self.reg(FSDIF_CLKDIV_OFFSET) & 0xff
+    }
+
+    /// Card data bus width in bits.
+    pub fn bus_width(&self) -> u32 {
+        let ctype = self.reg(FSDIF_CTYPE_OFFSET);
+        if ctype & (1 << 16) != 0 {
+            8
+        } else if ctype & 1 != 0 {
+            4
+        } else {
+            1
+        }
+    }
+
+    /// Command path state machine state.
+    pub fn cmd_fsm_state(&self) -> u32 {
+        (self.reg(FSDIF_STATUS_OFFSET) >> 4) & 0xf
+    }
+
+    /// Number of words in the FIFO.
+    pub fn fifo_count(&self) -> u32 {
+        (self.reg(FSDIF_STATUS_OFFSET) >> 17) & 0x1fff
+    }
+
+    /// Whether the card signals busy on DAT0.
+    pub fn data_busy(&self) -> bool {
+        self.reg(FSDIF_STATUS_OFFSET) & (1 << 9) != 0
+    }
+
+    /// Raw interrupt status.
+    pub fn raw_ints(&self) -> u32 {
+        self.reg(FSDIF_RAW_INTS_OFFSET)
+    }
+
+    /// Internal DMA controller state machine state.
+    pub fn dmac_fsm_state(&self) -> u32 {
+        (self.reg(FSDIF_DMAC_STATUS_OFFSET) >> 13) & 0xf
+    }
+}
+
+impl fmt::Debug for RegisterSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, _, value) in self.iter() {
+            map.key(&name).value(&format_args!("0x{:08x}", value));
+        }
+        map.finish()
+    }
+}
+
+impl fmt::Display for RegisterSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ctrl = self.reg(FSDIF_CNTRL_OFFSET);
+        writeln!(f, "cntrl    0x{:08x} {}", ctrl, BitNames(ctrl, &CTRL_NAMES))?;
+        writeln!(
+            f,
+            "pwren    0x{:08x} power {}",
+            self.reg(FSDIF_PWREN_OFFSET),
+            if self.reg(FSDIF_PWREN_OFFSET) & 1 != 0 {
+                "on"
+            } else {
+                "off"
+            }
+        )?;
+        let clkdiv = self.reg(FSDIF_CLKDIV_OFFSET);
+        writeln!(
+            f,
+            "clkdiv   0x{:08x} divider {} drv {} sample {}",
+            clkdiv,
+            self.clk_divider(),
+            (clkdiv >> 8) & 0xff,
+            (clkdiv >> 16) & 0xff
+        )?;
+        let clkena = self.reg(FSDIF_CLKENA_OFFSET);
+        writeln!(
+            f,
+            "clkena   0x{:08x} clock {}{}",
+            clkena,
+            if clkena & 1 != 0 { "on" } else { "off" },
+            if clkena & (1 << 16) != 0 {
+                ", low power"
+            } else {
+                ""
+            }
+        )?;
+        let tmout = self.reg(FSDIF_TMOUT_OFFSET);
+        writeln!(
+            f,
+            "tmout    0x{:08x} response {} data {} cycles",
+            tmout,
+            tmout & 0xff,
+            tmout >> 8
+        )?;
+        writeln!(
+            f,
+            "ctype    0x{:08x} {}-bit",
+            self.reg(FSDIF_CTYPE_OFFSET),
+            self.bus_width()
+        )?;
+        writeln!(
+            f,
+            "blksiz   {} bytes, bytcnt {} bytes",
+            self.reg(FSDIF_BLK_SIZ_OFFSET),
+            self.reg(FSDIF_BYT_CNT_OFFSET)
+        )?;
+        for (name, offset) in [
+            ("intmask", FSDIF_INT_MASK_OFFSET),
+            ("mintsts", FSDIF_MASKED_INTS_OFFSET),
+            ("rintsts", FSDIF_RAW_INTS_OFFSET),
+        ] {
+            let ints = self.reg(offset);
+            writeln!(
+                f,
+                "{:<8} 0x{:08x} {}",
+                name,
+                ints,
+                BitNames(ints, &INT_NAMES)
+            )?;
+        }
+        let cmd = self.reg(FSDIF_CMD_OFFSET);
+        writeln!(
+            f,
+            "cmd      0x{:08x} CMD{} arg 0x{:08x}{}{}{}",
+            cmd,
+            cmd & 0x3f,
+            self.reg(FSDIF_CMD_ARG_OFFSET),
+            if cmd & (1 << 31) != 0 { ", start" } else { "" },
+            if cmd & (1 << 9) != 0 { ", data" } else { "" },
+            if cmd & (1 << 10) != 0 { " write" } else { "" }
+        )?;
+        writeln!(
+            f,
+            "resp     0x{:08x} 0x{:08x} 0x{:08x} 0x{:08x}",
+            self.reg(FSDIF_RESP0_OFFSET),
+            self.reg(FSDIF_RESP1_OFFSET),
+            self.reg(FSDIF_RESP2_OFFSET),
+            self.reg(FSDIF_RESP3_OFFSET)
+        )?;
+        let status = self.reg(FSDIF_STATUS_OFFSET);
+        writeln!(
+            f,
+            "status   0x{:08x} cmd fsm {} ({}), fifo {} words{}{}{}, resp index {}",
+            status,
+            self.cmd_fsm_state(),
+            CMD_FSM_STATES[self.cmd_fsm_state() as usize],
+            self.fifo_count(),
+            if status & (1 << 2) != 0 { " empty" } else { "" },
+            if status & (1 << 3) != 0 { " full" } else { "" },
+            if self.data_busy() { ", card busy" } else { "" },
+            (status >> 11) & 0x3f
+        )?;
+        let fifoth = self.reg(FSDIF_FIFOTH_OFFSET);
+        writeln!(
+            f,
+            "fifoth   0x{:08x} tx mark {} rx mark {} burst {}",
+            fifoth,
+            fifoth & 0xfff,
+            (fifoth >> 16) & 0xfff,
+            (fifoth >> 28) & 0x7
+        )?;
+        writeln!(
+            f,
+            "cdetect  0x{:08x} card {}, wrtprt 0x{:08x}",
+            self.reg(FSDIF_CARD_DETECT_OFFSET),
+            if self.reg(FSDIF_CARD_DETECT_OFFSET) & 1 == 0 {
+                "present"
+            } else {
+                "absent"
+            },
+            self.reg(FSDIF_CARD_WRTPRT_OFFSET)
+        )?;
+        writeln!(
+            f,
+            "tcbcnt   {} bytes to card, tbbcnt {} bytes to fifo",
+            self.reg(FSDIF_TRAN_CARD_CNT_OFFSET),
+            self.reg(FSDIF_TRAN_FIFO_CNT_OFFSET)
+        )?;
+        let uhs = self.reg(FSDIF_UHS_REG_OFFSET);
+        writeln!(
+            f,
+            "uhsreg   0x{:08x} {}{}",
+            uhs,
+            if uhs & 1 != 0 { "1.8V" } else { "3.3V" },
+            if uhs & (1 << 16) != 0 { ", DDR" } else { "" }
+        )?;
+        writeln!(
+            f,
+            "cardrst  0x{:08x} card {}",
+            self.reg(FSDIF_CARD_RESET_OFFSET),
+            if self.reg(FSDIF_CARD_RESET_OFFSET) & 1 != 0 {
+                "running"
+            } else {
+                "in reset"
+            }
+        )?;
+        let busmode = self.reg(FSDIF_BUS_MODE_OFFSET);
+        writeln!(
+            f,
+            "busmode  0x{:08x} idma {}{}{}, burst {}",
+            busmode,
+            if busmode & (1 << 7) != 0 { "on" } else { "off" },
+            if busmode & 1 != 0 { ", soft reset" } else { "" },
+            if busmode & (1 << 1) != 0 {
+                ", fixed burst"
+            } else {
+                ""
+            },
+            (busmode >> 8) & 0x7
+        )?;
+        let idsts = self.reg(FSDIF_DMAC_STATUS_OFFSET);
+        let dmac_fsm = self.dmac_fsm_state() as usize;
+        writeln!(
+            f,
+            "idsts    0x{:08x} {}, fsm {} ({}), bus error {}",
+            idsts,
+            BitNames(idsts, &IDSTS_NAMES),
+            dmac_fsm,
+            DMAC_FSM_STATES.get(dmac_fsm).copied().unwrap_or("unknown"),
+            (idsts >> 10) & 0x7
+        )?;
+        let idinten = self.reg(FSDIF_DMAC_INT_EN_OFFSET);
+        writeln!(
+            f,
+            "idinten  0x{:08x} {}",
+            idinten,
+            BitNames(idinten, &IDSTS_NAMES)
+        )?;
+        writeln!(
+            f,
+            "desc     list 0x{:08x}{:08x} current 0x{:08x}{:08x} buffer 0x{:08x}{:08x}",
+            self.reg(FSDIF_DESC_LIST_ADDRH_OFFSET),
+            self.reg(FSDIF_DESC_LIST_ADDRL_OFFSET),
+            self.reg(FSDIF_CUR_DESC_ADDRH_OFFSET),
+            self.reg(FSDIF_CUR_DESC_ADDRL_OFFSET),
+            self.reg(FSDIF_CUR_BUF_ADDRH_OFFSET),
+            self.reg(FSDIF_CUR_BUF_ADDRL_OFFSET)
+        )?;
+        for (name, offset) in [
+            ("cksts", FSDIF_CKSTS_OFFSET),
+            ("debnce", FSDIF_DEBNCE_OFFSET),
+            ("uid", FSDIF_UID_OFFSET),
+            ("vid", FSDIF_VID_OFFSET),
+            ("hwconf", FSDIF_HWCONF_OFFSET),
+            ("cardthr", FSDIF_CARD_THRCTL_OFFSET),
+            ("clksrc", FSDIF_CLK_SRC_OFFSET),
+            ("emmcddr", FSDIF_EMMC_DDR_REG_OFFSET),
+            ("shift", FSDIF_ENABLE_SHIFT_OFFSET),
+        ] {
+            writeln!(f, "{:<8} 0x{:08x}", name, self.reg(offset))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+//! # Streaming Transfers
+//!
+//! Open-ended CMD18/CMD25 over a closed ring of equally sized chunks. The
+//! IDMAC walks the ring for as long as the command runs and hands each chunk
+//! back once it's moved; the CPU arms it again and demands a poll, which
+//! resumes the IDMAC if it suspended on a chunk it didn't own. CMD12 ends
+//! the transfer. Only built with the `dma` feature.
+
+use log::*;
+
+use super::MCI;
+use super::constants::*;
+use super::err::*;
+use super::mci_cmddata::MCICmdData;
+use super::regs::*;
+use crate::regs::Backoff;
+
+impl MCI {
+    /// Start an open-ended transfer over `chunks`, the bus addresses of
+    /// `chunk_len` byte buffers, the first `armed` of them owned by the IDMAC.
+    ///
+    /// `cmd_data` is the CMD18/CMD25 to send, with no data attached. Returns
+    /// once the card answered the command, with the response in `cmd_data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The device is not initialized or not in DMA mode
+    /// - Card is not detected (for removable media) or busy
+    /// - A buffer is not aligned to 4 bytes, or the ring needs more descriptors than there are
+    /// - The command fails or times out
+    pub fn stream_start(
+        &mut self,
+        cmd_data: &mut MCICmdData,
+        chunks: &[usize],
+        chunk_len: u32,
+        blksz: u32,
+        armed: usize,
+    ) -> MCIResult {
+        cmd_data.success_set(false);
+        self.cur_cmd_set(cmd_data);
+
+        if !self.is_ready {
+            error!("Device is not yet initialized!");
+            return Err(MCIError::NotInit);
+        }
+
+        if self.config.trans_mode() != MCITransMode::DMA {
+            error!("Device is not configured in DMA transfer mode!");
+            return Err(MCIError::InvalidState);
+        }
+
+        if !self.config.non_removable() && !self.check_if_card_exist() {
+            error!("card is not detected !!!");
+            return Err(MCIError::NoCard);
+        }
+
+        if blksz == 0 || !chunk_len.is_multiple_of(blksz) {
+            error!("stream chunk of {} bytes is not whole blocks!", chunk_len);
+            return Err(MCIError::InvalidState);
+        }
+        if let Some(addr) = chunks.iter().find(|addr| !addr.is_multiple_of(4)) {
+            error!("DMA stream chunk 0x{:x} do not align to 4!", addr);
+            return Err(MCIError::DmaBufUnalign);
+        }
+
+        self.poll_wait_busy_card()?;
+
+        let reg = self.config.reg().clone();
+        reg.write_reg(MCIRawInts::from_bits_truncate(0xFFFFE));
+        self.ctrl_reset(MCICtrl::FIFO_RESET | MCICtrl::DMA_RESET)?;
+        reg.modify_reg(|reg| MCICtrl::USE_INTERNAL_DMAC | reg);
+        reg.modify_reg(|reg| MCIBusMode::DE | reg);
+
+        self.interrupt_mask_set(
+            MCIIntrType::GeneralIntr,
+            MCIIntMask::INTS_DATA_MASK.bits(),
+            true,
+        );
+        self.interrupt_mask_set(MCIIntrType::DmaIntr, MCIDMACIntEn::INTS_MASK.bits(), true);
+
+        self.desc_list.stream_prepare(chunks, chunk_len, armed)?;
+        debug!(
+            "DMA stream over {} chunk(s) of {} bytes, {} armed",
+            chunks.len(),
+            chunk_len,
+            armed
+        );
+        self.dump_dma_descriptor(self.desc_list.desc_in_use);
+
+        self.descriptor_set(self.desc_list.first_desc_dma);
+        /* a zero byte count keeps the transfer going until CMD12 */
+        self.trans_bytes_set(0);
+        self.blksize_set(blksz);
+
+        self.cmd_transfer(cmd_data)?;
+
+        /* the data keeps flowing, only wait for the response */
+        let mut backoff = Backoff::new(self.config.cmd_timeout());
+        loop {
+            let raw_ints = reg.read_reg::<MCIRawInts>();
+            if let Some(err) = self.stream_error_get() {
+                error!(
+                    "CMD-{} failed: {:?}, raw ints: 0x{:x}",
+                    cmd_data.cmdidx(),
+                    err,
+                    raw_ints.bits()
+                );
+                cmd_data.raw_ints_set(raw_ints.bits());
+                return Err(err);
+            }
+            if raw_ints.contains(MCIRawInts::CMD_BIT) {
+                break;
+            }
+            if !backoff.snooze() {
+                error!(
+                    "Wait stream command done timeout, raw ints: 0x{:x}!",
+                    raw_ints.bits()
+                );
+                cmd_data.raw_ints_set(raw_ints.bits());
+                return Err(MCIError::CmdTimeout);
+            }
+        }
+        reg.write_reg(MCIRawInts::CMD_BIT);
+
+        let response = cmd_data.get_mut_response();
+        response[0] = reg.read_reg::<MCIResp0>().bits();
+        trace!("    resp: 0x{:x}", response[0]);
+
+        cmd_data.success_set(true);
+        self.prev_cmd = cmd_data.cmdidx();
+        Ok(())
+    }
+
+    /// Whether the IDMAC is done with `chunk` of the running stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns the transfer error if the data phase or the IDMAC failed
+    /// before the chunk was done.
+    pub fn stream_chunk_done(&self, chunk: usize) -> MCIResult<bool> {
+        /* a chunk already handed back is good whatever failed after it */
+        if self.desc_list.chunk_done(chunk)? {
+            return Ok(true);
+        }
+        if let Some(err) = self.stream_error_get() {
+            error!(
+                "DMA stream failed: {:?}, raw ints: 0x{:x}, dma status: 0x{:x}",
+                err,
+                self.raw_status_get().bits(),
+                self.dma_status_get().bits()
+            );
+            return Err(err);
+        }
+        Ok(false)
+    }
+
+    /// Hand `chunk` back to the IDMAC and wake it up if it suspended on it.
+    pub fn stream_chunk_arm(&mut self, chunk: usize) -> MCIResult {
+        self.desc_list.chunk_arm(chunk)?;
+        unsafe { dsb() };
+
+        let reg = self.config.reg();
+        /* ack the suspension before resuming, so a new one shows up */
+        reg.write_reg(MCIDMACStatus::DU_BIT0 | MCIDMACStatus::DU_BIT1);
+        reg.write_reg(MCIPollDemand::PD);
+        Ok(())
+    }
+
+    /// End the running stream with `cmd_data`, the CMD12 to send.
+    ///
+    /// For a write the chunks the card should get must already be done, the
+    /// command goes out once the FIFO has drained. The controller is left
+    /// ready for the next transfer even if stopping fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the FIFO doesn't drain, or the CMD12 fails or times out.
+    pub fn stream_stop(&mut self, cmd_data: &mut MCICmdData, write: bool) -> MCIResult {
+        cmd_data.success_set(false);
+        self.cur_cmd_set(cmd_data);
+
+        let result = self.stream_stop_send(cmd_data, write);
+
+        /* what the IDMAC still owns is dropped with the FIFO */
+        let reset = self.ctrl_reset(MCICtrl::FIFO_RESET | MCICtrl::DMA_RESET);
+        self.idma_reset();
+        self.raw_status_clear();
+        self.dma_status_clear();
+        self.desc_list.stream_end();
+
+        self.interrupt_mask_set(
+            MCIIntrType::GeneralIntr,
+            (MCIIntMask::INTS_CMD_MASK | MCIIntMask::INTS_DATA_MASK).bits(),
+            false,
+        );
+        self.interrupt_mask_set(MCIIntrType::DmaIntr, MCIDMACIntEn::INTS_MASK.bits(), false);
+        self.prev_cmd = cmd_data.cmdidx();
+
+        result?;
+        reset?;
+        cmd_data.success_set(true);
+        Ok(())
+    }
+
+    fn stream_stop_send(&mut self, cmd_data: &mut MCICmdData, write: bool) -> MCIResult {
+        let reg = self.config.reg().clone();
+
+        if write {
+            reg.poll_until(
+                |reg: MCIStatus| reg.contains(MCIStatus::FIFO_EMPTY),
+                self.config.data_timeout(),
+            )
+            .inspect_err(|_| error!("Wait stream FIFO drain timeout!"))?;
+        }
+
+        /* the data state machine is busy on purpose, don't wait for it */
+        reg.write_reg(MCICmdArg::from_bits_truncate(cmd_data.cmdarg()));
+        unsafe { dsb() };
+        reg.write_reg(MCICmd::START | self.cmd_raw_get(cmd_data));
+        reg.poll_until(
+            |reg: MCICmd| !reg.contains(MCICmd::START),
+            self.config.cmd_timeout(),
+        )?;
+
+        let wait_bits = (MCIRawInts::CMD_BIT | MCIRawInts::DTO_BIT).bits();
+        let resp_err = (MCIRawInts::RTO_BIT | MCIRawInts::RCRC_BIT | MCIRawInts::RE_BIT).bits();
+        let mut backoff = Backoff::new(self.config.data_timeout());
+        let raw_ints = loop {
+            let raw_ints = reg.read_reg::<MCIRawInts>().bits();
+            if raw_ints & wait_bits == wait_bits || raw_ints & resp_err != 0 {
+                break raw_ints;
+            }
+            if !backoff.snooze() {
+                error!("Wait stream stop timeout, raw ints: 0x{:x}!", raw_ints);
+                cmd_data.raw_ints_set(raw_ints);
+                return Err(MCIError::CmdTimeout);
+            }
+        };
+        cmd_data.raw_ints_set(raw_ints);
+
+        /* data errors on the block cut short by the stop are expected */
+        if let Some(err) = MCIError::from_raw_ints(raw_ints & resp_err) {
+            error!(
+                "CMD-{} failed: {:?}, raw ints: 0x{:x}",
+                cmd_data.cmdidx(),
+                err,
+                raw_ints
+            );
+            return Err(err);
+        }
+
+        let response = cmd_data.get_mut_response();
+        response[0] = reg.read_reg::<MCIResp0>().bits();
+        trace!("    resp: 0x{:x}", response[0]);
+        Ok(())
+    }
+
+    /* like transfer_error_get, but the IDMAC suspending on an unarmed chunk is no error */
+    fn stream_error_get(&self) -> Option<MCIError> {
+        if let Some(err) = MCIError::from_raw_ints(self.raw_status_get().bits()) {
+            return Some(err);
+        }
+        let dma_status = self.dma_status_get() & (MCIDMACStatus::FBE | MCIDMACStatus::CES);
+        MCIError::from_dma_status(dma_status.bits())
+    }
+}
@@ -0,0 +1,129 @@
+//! # Command Trace
+//!
+//! Ring of the last [`MCI_CMD_TRACE_DEPTH`] commands issued on the controller,
+//! kept for post-mortem debugging. Only built with the `cmd-trace` feature.
+
+use core::fmt;
+use core::time::Duration;
+
+use log::warn;
+
+use super::constants::*;
+use super::err::MCIResult;
+use super::mci_cmddata::MCICmdData;
+
+/// One command recorded by the [`MCICmdTrace`].
+#[derive(Debug, Clone, Copy)]
+pub struct MCICmdTraceEntry {
+    /// Command index
+    pub cmd_index: u32,
+    /// Command argument
+    pub argument: u32,
+    /// Command flags
+    pub flags: MCICmdFlag,
+    /// Response words, zero when the command has no response
+    pub response: [u32; 4],
+    /// Raw interrupt status seen at completion
+    pub raw_ints: u32,
+    /// Time from issuing the command to its completion
+    pub duration: Duration,
+    /// Outcome of the command
+    pub result: MCIResult,
+}
+
+impl fmt::Display for MCICmdTraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CMD{} arg 0x{:08x} flags 0x{:x} resp 0x{:08x} 0x{:08x} 0x{:08x} 0x{:08x} raw ints 0x{:x} {}us ",
+            self.cmd_index,
+            self.argument,
+            self.flags.bits(),
+            self.response[0],
+            self.response[1],
+            self.response[2],
+            self.response[3],
+            self.raw_ints,
+            self.duration.as_micros()
+        )?;
+        match self.result {
+            Ok(()) => f.write_str("ok"),
+            Err(err) => write!(f, "{:?}", err),
+        }
+    }
+}
+
+/// Fixed-size ring of the most recent commands, the oldest entry is overwritten.
+pub struct MCICmdTrace {
+    entries: [Option<MCICmdTraceEntry>; MCI_CMD_TRACE_DEPTH],
+    /* slot the next entry goes to */
+    next: usize,
+}
+
+impl MCICmdTrace {
+    pub(crate) const fn new() -> Self {
+        Self {
+            entries: [None; MCI_CMD_TRACE_DEPTH],
+            next: 0,
+        }
+    }
+
+    pub(crate) fn record(&mut self, cmd_data: &MCICmdData, duration: Duration, result: MCIResult) {
+        let mut response = [0; 4];
+        response.copy_from_slice(&cmd_data.get_response()[..4]);
+        self.entries[self.next] = Some(MCICmdTraceEntry {
+            cmd_index: cmd_data.cmdidx(),
+            argument: cmd_data.cmdarg(),
+            flags: *cmd_data.flag(),
+            response,
+            raw_ints: cmd_data.raw_ints(),
+            duration,
+            result,
+        });
+        self.next = (self.next + 1) % MCI_CMD_TRACE_DEPTH;
+    }
+
+    /// Number of recorded entries.
+    pub fn len(&self) -> usize {
+        self.entries.iter().filter(|e| e.is_some()).count()
+    }
+
+    /// Whether no command was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Recorded entries, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &MCICmdTraceEntry> {
+        let (newer, older) = self.entries.split_at(self.next);
+        older.iter().chain(newer.iter()).filter_map(|e| e.as_ref())
+    }
+
+    /// Remove and return the recorded entries, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = MCICmdTraceEntry> + '_ {
+        let start = self.next;
+        self.next = 0;
+        (0..MCI_CMD_TRACE_DEPTH)
+            .filter_map(move |i| self.entries[(start + i) % MCI_CMD_TRACE_DEPTH].take())
+    }
+
+    /// Forget all recorded entries.
+    pub fn clear(&mut self) {
+        self.entries = [None; MCI_CMD_TRACE_DEPTH];
+        self.next = 0;
+    }
+
+    /// Log the recorded entries, oldest first.
+    pub fn dump(&self) {
+        warn!("last {} command(s):", self.len());
+        for entry in self.iter() {
+            warn!("    {}", entry);
+        }
+    }
+}
+
+impl Default for MCICmdTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -16,6 +16,8 @@
 //! - **mci_data**: Data transfer structures
 //! - **mci_dma**: DMA transfer support (feature-gated)
 //! - **mci_config**: Configuration structures
+//! - **mci_snapshot**: Decoded register snapshots
+//! - **mci_trace**: Ring of the last commands (feature-gated)
 //!
 //! Note: Submodules with duplicate names should not be marked as pub
 pub mod constants;
@@ -32,13 +34,18 @@ mod mci_hardware;
 mod mci_intr;
 #[cfg(feature = "pio")]
 mod mci_pio;
+mod mci_snapshot;
+#[cfg(feature = "dma")]
+mod mci_stream;
 mod mci_timing;
+#[cfg(feature = "cmd-trace")]
+mod mci_trace;
 
 pub use err::*;
 
 use constants::*;
 #[cfg(feature = "dma")]
-use dma_api::DSlice;
+use dma_api::DVec;
 
 #[cfg(feature = "dma")]
 use mci_dma::{FSdifIDmaDesc, FSdifIDmaDescList};
@@ -48,9 +55,16 @@ use regs::*;
 
 pub use mci_cmddata::*;
 pub use mci_config::*;
+#[cfg(feature = "dma")]
+pub use mci_dma::MCIDmaSegment;
+pub use mci_snapshot::{RegisterChange, RegisterSnapshot};
 pub use mci_timing::*;
+#[cfg(feature = "cmd-trace")]
+pub use mci_trace::{MCICmdTrace, MCICmdTraceEntry};
 
-use crate::{IoPad, regs::*, sleep};
+use crate::osa::OsaEvent;
+use crate::{IoPad, regs::*};
+use alloc::sync::Arc;
 use core::time::Duration;
 
 /// MCI (Memory Card Interface) controller driver.
@@ -78,16 +92,35 @@ pub struct MCI {
     curr_timing: MCITiming,
     cur_cmd: Option<MCICmdData>,
     io_pad: Option<IoPad>,
+    irq_event: Option<Arc<OsaEvent>>, // Notified by the interrupt handler, waits poll without it
     #[cfg(feature = "dma")]
     desc_list: FSdifIDmaDescList,
+    #[cfg(feature = "cmd-trace")]
+    cmd_trace: MCICmdTrace,
+    #[cfg(feature = "pio")]
+    pio_offset: usize, // Words of the current PIO transfer moved through the FIFO
 }
 
 impl MCI {
     const SWITCH_VOLTAGE: u32 = 11;
     const EXT_APP_CMD: u32 = 55;
 
-    pub(crate) fn relax_handler() {
-        sleep(Duration::from_micros(10));
+    /// Fail the PIO transfer if the controller flagged an error, acking the status.
+    #[cfg(feature = "pio")]
+    fn pio_error_check(&self, cmd_data: &mut MCICmdData) -> MCIResult {
+        if let Some(err) = self.transfer_error_get() {
+            let raw_ints = self.raw_status_get().bits();
+            error!(
+                "CMD-{} failed: {:?}, raw ints: 0x{:x}",
+                cmd_data.cmdidx(),
+                err,
+                raw_ints
+            );
+            cmd_data.raw_ints_set(raw_ints);
+            self.raw_status_clear();
+            return Err(err);
+        }
+        Ok(())
     }
 
     pub(crate) fn new(config: MCIConfig) -> Self {
@@ -98,8 +131,13 @@ impl MCI {
             curr_timing: MCITiming::new(),
             cur_cmd: None,
             io_pad: None,
+            irq_event: None,
             #[cfg(feature = "dma")]
             desc_list: FSdifIDmaDescList::new(),
+            #[cfg(feature = "cmd-trace")]
+            cmd_trace: MCICmdTrace::new(),
+            #[cfg(feature = "pio")]
+            pio_offset: 0,
         }
     }
 
@@ -111,8 +149,13 @@ impl MCI {
             curr_timing: MCITiming::new(),
             cur_cmd: None,
             io_pad: None,
+            irq_event: None,
             #[cfg(feature = "dma")]
             desc_list: FSdifIDmaDescList::new(),
+            #[cfg(feature = "cmd-trace")]
+            cmd_trace: MCICmdTrace::new(),
+            #[cfg(feature = "pio")]
+            pio_offset: 0,
         }
     }
 }
@@ -126,6 +169,11 @@ impl MCI {
         self.io_pad = Some(iopad);
     }
 
+    /// Whether the device is initialized.
+    pub fn is_ready(&self) -> bool {
+        self.is_ready
+    }
+
     /// Take and remove the I/O pad from this MCI instance.
     ///
     /// Returns `None` if no I/O pad was set.
@@ -133,8 +181,35 @@ impl MCI {
         self.io_pad.take()
     }
 
+    /// Set how long to wait for commands and data transfers to complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd_timeout` - Maximum time to wait for a command to complete
+    /// * `data_timeout` - Maximum time to wait for a data transfer or a busy card
+    pub fn timeouts_set(&mut self, cmd_timeout: Duration, data_timeout: Duration) {
+        self.config.cmd_timeout_set(cmd_timeout);
+        self.config.data_timeout_set(data_timeout);
+    }
+
+    /// Commands recorded by the command trace, see [`MCICmdTrace`].
+    #[cfg(feature = "cmd-trace")]
+    pub fn cmd_trace(&self) -> &MCICmdTrace {
+        &self.cmd_trace
+    }
+
+    /// Mutable access to the command trace, to drain or clear it.
+    #[cfg(feature = "cmd-trace")]
+    pub fn cmd_trace_mut(&mut self) -> &mut MCICmdTrace {
+        &mut self.cmd_trace
+    }
+
     // TODO: Used clone first to avoid ownership issues
     pub fn cur_cmd_set(&mut self, cmd: &MCICmdData) {
+        /* ack the voltage switch interrupt of CMD11 before it fails the next command */
+        if self.volt_switch_pending() {
+            self.config.reg().write_reg(MCIRawInts::HTO_BIT);
+        }
         self.cur_cmd = Some(cmd.clone());
     }
 
@@ -166,12 +241,31 @@ impl MCI {
     /// Deinitialize the SDIF controller instance.
     ///
     /// This function performs cleanup operations:
+    /// - Stops a transfer still running (FIFO, DMA and internal DMA reset)
     /// - Disables interrupts (controller and DMA)
     /// - Clears interrupt status
     /// - Powers off the card
     /// - Disables card clock
     /// - Resets voltage to default 3.3V
+    ///
+    /// Does nothing if the device is not initialized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the FIFO or DMA reset fails, the device is
+    /// deinitialized anyway.
     pub fn config_deinit(&mut self) -> MCIResult {
+        if !self.is_ready {
+            return Ok(());
+        }
+
+        let reset = if self.config.trans_mode() == MCITransMode::DMA {
+            self.idma_reset();
+            self.ctrl_reset(MCICtrl::FIFO_RESET | MCICtrl::DMA_RESET)
+        } else {
+            self.ctrl_reset(MCICtrl::FIFO_RESET)
+        };
+
         self.interrupt_mask_set(MCIIntrType::GeneralIntr, MCIIntMask::ALL_BITS.bits(), false); /* Disable controller interrupt bits */
         self.interrupt_mask_set(MCIIntrType::DmaIntr, MCIDMACIntEn::ALL_BITS.bits(), false); /* Disable DMA interrupt bits */
 
@@ -186,11 +280,16 @@ impl MCI {
         reg.clear_reg(MCIUhsReg::VOLT_180); /* Restore to 3.3V default voltage */
 
         self.is_ready = false;
-        Ok(())
+        reset
     }
 
     /// Setup DMA descriptor list for SDIF controller instance.
     ///
+    /// The controller keeps `desc` until [`idma_list_take`](Self::idma_list_take)
+    /// or drop, it must be mapped for the device (`dma_api`) and start on a
+    /// cache line. The descriptors are chained here once, transfers only fill
+    /// in their buffers.
+    ///
     /// # Features
     ///
     /// This function is only available when the `dma` feature is enabled.
@@ -200,8 +299,9 @@ impl MCI {
     /// Returns an error if:
     /// - The device is not initialized
     /// - The device is not configured in DMA transfer mode
+    /// - The descriptor list is empty or not aligned to a cache line
     #[cfg(feature = "dma")]
-    pub fn set_idma_list(&mut self, desc: &PoolBuffer, desc_num: u32) -> MCIResult {
+    pub fn set_idma_list(&mut self, desc: DVec<FSdifIDmaDesc>) -> MCIResult {
         if !self.is_ready {
             error!("Device is not yet initialized!");
             return Err(MCIError::NotInit);
@@ -212,30 +312,28 @@ impl MCI {
             return Err(MCIError::InvalidState);
         }
 
-        // TODO: Not very elegant, consider modifying later
-        let desc_vec = unsafe {
-            core::mem::ManuallyDrop::new(Vec::from_raw_parts(
-                desc.addr().as_ptr(),
-                desc_num as usize,
-                desc_num as usize,
-            ))
-        };
-        let slice = DSlice::from(&desc_vec[..]); // Get physical address
-        error!(
+        debug!(
             "set idma list, desc addr: 0x{:x}, desc num: {}",
-            slice.bus_addr(),
-            desc_num
+            desc.bus_addr(),
+            desc.len()
         );
-        self.desc_list.first_desc_dma = slice.bus_addr() as usize;
-        self.desc_list.first_desc = desc.addr().as_ptr() as *mut FSdifIDmaDesc;
-        self.desc_list.desc_num = desc_num;
-        self.desc_list.desc_trans_sz = FSDIF_IDMAC_MAX_BUF_SIZE;
+        self.desc_list.attach(desc)?;
 
         debug!("idma_list set success!");
 
         Ok(())
     }
 
+    /// Take back the DMA descriptor list set with [`set_idma_list`](Self::set_idma_list).
+    ///
+    /// # Features
+    ///
+    /// This function is only available when the `dma` feature is enabled.
+    #[cfg(feature = "dma")]
+    pub fn idma_list_take(&mut self) -> Option<DVec<FSdifIDmaDesc>> {
+        self.desc_list.detach()
+    }
+
     /// Set the card clock frequency.
     ///
     /// This function configures the card clock to the specified frequency.
@@ -328,6 +426,16 @@ impl MCI {
         Ok(())
     }
 
+    /// Stop or restart the card clock, keeping its frequency.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the controller doesn't take the clock update.
+    pub fn clock_gate_set(&self, gated: bool) -> MCIResult {
+        self.clock_set(!gated);
+        self.private_cmd_send(MCICmd::UPD_CLK, 0)
+    }
+
     /// Start command and data transfer in DMA mode.
     ///
     /// This function initiates a DMA-based transfer. It will:
@@ -350,7 +458,7 @@ impl MCI {
     #[cfg(feature = "dma")]
     pub fn dma_transfer(&mut self, cmd_data: &mut MCICmdData) -> MCIResult {
         cmd_data.success_set(false);
-        self.cur_cmd_set(&cmd_data);
+        self.cur_cmd_set(cmd_data);
 
         if !self.is_ready {
             error!("Device is not yet initialized!");
@@ -391,15 +499,16 @@ impl MCI {
         }
 
         // transfer command
-        self.cmd_transfer(&cmd_data)?;
+        self.cmd_transfer(cmd_data)?;
 
         Ok(())
     }
 
-    /// Wait for DMA transfer to finish by polling.
+    /// Wait for DMA transfer to finish.
     ///
-    /// This function polls the interrupt status registers until the transfer
-    /// is complete or a timeout occurs.
+    /// This function waits on the interrupt status registers until the transfer
+    /// is complete or a timeout occurs, sleeping on the interrupts with an
+    /// event set by [`irq_event_set`](Self::irq_event_set).
     ///
     /// # Features
     ///
@@ -413,11 +522,15 @@ impl MCI {
     /// - Transfer timeout occurs
     #[cfg(feature = "dma")]
     pub fn poll_wait_dma_end(&mut self, cmd_data: &mut MCICmdData) -> MCIResult {
-        let wait_bits = if cmd_data.get_data().is_none() {
+        let mut wait_bits = if cmd_data.get_data().is_none() {
             MCIIntMask::CMD_BIT.bits()
         } else {
             MCIIntMask::CMD_BIT.bits() | MCIIntMask::DTO_BIT.bits()
         };
+        /* the card is not done before the auto CMD12 is */
+        if cmd_data.flag().contains(MCICmdFlag::NEED_AUTO_STOP) {
+            wait_bits |= MCIIntMask::ACD_BIT.bits();
+        }
         let mut reg_val;
 
         if !self.is_ready {
@@ -430,30 +543,52 @@ impl MCI {
             return Err(MCIError::InvalidState);
         }
 
-        /* wait command done or data timeout */
-        let mut delay = RETRIES_TIMEOUT;
+        /* wait command done, data timeout or an error */
+        let timeout = if cmd_data.get_data().is_none() {
+            self.config.cmd_timeout()
+        } else {
+            self.config.data_timeout()
+        };
+        let mut backoff = Backoff::new(timeout);
+        let mut timed_out = false;
+        let mut transfer_err;
         loop {
             reg_val = self.config.reg().read_reg::<MCIRawInts>().bits();
-            if delay % 1000 == 0 {
-                debug!("polling dma end, reg_val = 0x{:x}", reg_val);
+            transfer_err = self.transfer_error_get();
+            if wait_bits & reg_val == wait_bits || transfer_err.is_some() {
+                break;
             }
-            // TODO: relax handler?
-
-            delay -= 1;
-            if wait_bits & reg_val == wait_bits || delay == 0 {
+            if !self.ints_wait(&mut backoff, wait_bits | self.ints_err_mask().bits()) {
+                timed_out = true;
                 break;
             }
         }
 
         /* clear status to ack data done */
         self.raw_status_clear();
+        self.dma_status_clear();
+        #[cfg(feature = "cmd-trace")]
+        cmd_data.raw_ints_set(reg_val);
+
+        if let Some(err) = transfer_err {
+            error!(
+                "CMD-{} failed: {:?}, raw ints: 0x{:x}",
+                cmd_data.cmdidx(),
+                err,
+                reg_val
+            );
+            cmd_data.raw_ints_set(reg_val);
+            return Err(err);
+        }
 
-        if wait_bits & reg_val != wait_bits && delay <= 0 {
+        if timed_out {
             error!("Wait command done timeout, raw ints: 0x{:x}!", reg_val);
+            cmd_data.raw_ints_set(reg_val);
             return Err(MCIError::CmdTimeout);
         }
 
         if cmd_data.get_data().is_some() {
+            self.desc_list.reclaim()?;
             let read = cmd_data.flag().contains(MCICmdFlag::READ_DATA);
             if !read {
                 unsafe {
@@ -481,14 +616,14 @@ impl MCI {
     /// - PIO mode is not configured
     /// - Card is not detected (for removable media)
     /// - Card is busy
-    /// - Data size exceeds FIFO capacity (0x800 bytes)
     #[cfg(feature = "pio")]
-    pub fn pio_transfer(&self, cmd_data: &mut MCICmdData) -> MCIResult {
+    pub fn pio_transfer(&mut self, cmd_data: &mut MCICmdData) -> MCIResult {
         #[cfg(feature = "pio")]
         let read = cmd_data.flag().contains(MCICmdFlag::READ_DATA);
-        let reg = self.config.reg();
+        let reg = self.config.reg().clone();
 
         cmd_data.success_set(false);
+        self.cur_cmd_set(cmd_data);
 
         if !self.is_ready {
             error!("device is not yet initialized!!!");
@@ -513,40 +648,39 @@ impl MCI {
         self.ctrl_reset(MCICtrl::FIFO_RESET)?;
         reg.clear_reg(MCIBusMode::DE);
 
-        /* transfer data */
+        /* transfer data, longer than the FIFO it's streamed on the watermarks */
+        self.pio_offset = 0;
         if let Some(data) = cmd_data.get_mut_data() {
-            /* while in PIO mode, max data transferred is 0x800 */
-            if data.datalen() > MCI_MAX_FIFO_CNT {
-                error!(
-                    "Fifo do not support writing more than {:x}.",
-                    MCI_MAX_FIFO_CNT
-                );
-                return Err(MCIError::NotSupport);
-            }
-
             /* set transfer data length and block size */
             self.trans_bytes_set(data.datalen());
             self.blksize_set(data.blksz());
 
-            /* if need to write, write to fifo before send command */
-            #[cfg(feature = "pio")]
-            if !read {
-                /* invalide buffer for data to write */
+            if read {
+                if let Some(buf) = data.buf_mut() {
+                    buf.clear();
+                }
+            } else {
+                /* if need to write, fill the fifo before send command */
                 unsafe { dsb() };
-                self.pio_write_data(data)?;
+                reg.write_reg(MCICmd::DAT_WRITE);
+                self.pio_fifo_fill(data)?;
             }
         }
         self.cmd_transfer(cmd_data)?;
         Ok(())
     }
 
-    /// Wait for PIO transfer to finish by polling.
+    /// Wait for PIO transfer to finish.
     ///
-    /// This function polls the interrupt status registers until:
+    /// This function waits on the interrupt status registers until:
     /// - Command transfer is complete
     /// - Data transfer is complete (if data is present)
     ///
-    /// For read operations, data is read from FIFO after the command completes.
+    /// During the data phase the FIFO is drained or refilled whenever it
+    /// crosses the RX/TX watermark, so transfers aren't limited by its size.
+    ///
+    /// With an event set by [`irq_event_set`](Self::irq_event_set) it sleeps
+    /// on the command done, RXDR/TXDR and DTO interrupts instead of polling.
     ///
     /// # Features
     ///
@@ -560,8 +694,7 @@ impl MCI {
     /// - Transfer timeout occurs
     #[cfg(feature = "pio")]
     pub fn poll_wait_pio_end(&mut self, cmd_data: &mut MCICmdData) -> MCIResult {
-        let read = cmd_data.flag().contains(MCICmdFlag::READ_DATA);
-        let reg = self.config.reg();
+        let reg = self.config.reg().clone();
 
         if !self.is_ready {
             error!("device is not yet initialized!!!");
@@ -574,34 +707,24 @@ impl MCI {
         }
 
         trace!("wait for PIO cmd to finish ...");
-        if let Err(err) = reg.retry_for(
-            |reg: MCIRawInts| {
-                let result = reg.contains(MCIRawInts::CMD_BIT);
-                MCI::relax_handler();
-                result
-            },
-            Some(RETRIES_TIMEOUT),
-        ) {
-            error!(
-                "wait cmd done timeout, raw ints: 0x{:x}",
-                self.raw_status_get()
-            );
+        let wait_mask = (MCIRawInts::CMD_BIT | self.ints_err_mask()).bits();
+        if let Err(err) = self.raw_ints_wait(wait_mask, self.config.cmd_timeout()) {
+            let raw_ints = self.raw_status_get().bits();
+            error!("wait cmd done timeout, raw ints: 0x{:x}", raw_ints);
+            cmd_data.raw_ints_set(raw_ints);
             return Err(err);
         }
+        self.pio_error_check(cmd_data)?;
+        #[cfg(feature = "cmd-trace")]
+        cmd_data.raw_ints_set(self.raw_status_get().bits());
 
-        /* if need to read data, read fifo after send command */
-        if cmd_data.get_data().is_some() && read {
-            trace!("wait for PIO data to read ...");
-            if let Err(err) = reg.retry_for(
-                |reg| {
-                    MCI::relax_handler();
-                    (MCIRawInts::DTO_BIT & reg).bits() != 0
-                },
-                Some(RETRIES_TIMEOUT),
-            ) {
-                self.raw_status_clear();
-                return Err(err);
-            }
+        /* move the data through the fifo until data transfer over */
+        if cmd_data.get_data().is_some() {
+            trace!("wait for PIO data transfer ...");
+            self.pio_data_stream(cmd_data)?;
+            self.auto_stop_wait(cmd_data)?;
+            #[cfg(feature = "cmd-trace")]
+            cmd_data.raw_ints_set(cmd_data.raw_ints() | self.raw_status_get().bits());
 
             /* clear status to ack */
             self.raw_status_clear();
@@ -660,55 +783,17 @@ impl MCI {
         Ok(())
     }
 
+    /// Capture and decode all register values of the SDIF instance.
+    pub fn register_snapshot(&self) -> RegisterSnapshot {
+        RegisterSnapshot::capture(self.config.reg())
+    }
+
     /// Dump all register values of the SDIF instance.
     ///
-    /// This function logs the current values of all hardware registers
-    /// for debugging purposes.
+    /// This function logs the decoded values of all hardware registers
+    /// for debugging purposes, see [`register_snapshot`](Self::register_snapshot).
     pub fn register_dump(&self) {
-        let reg = self.config.reg();
-        warn!("cntrl: 0x{:x}", reg.read_reg::<MCICtrl>());
-        warn!("pwren: 0x{:x}", reg.read_reg::<MCIPwrEn>());
-        warn!("clkdiv: 0x{:x}", reg.read_reg::<MCIClkDiv>());
-        warn!("clkena: 0x{:x}", reg.read_reg::<MCIClkEn>());
-        warn!("tmout: 0x{:x}", reg.read_reg::<MCITimeout>());
-        warn!("ctype: 0x{:x}", reg.read_reg::<MCICType>());
-        warn!("blksz: 0x{:x}", reg.read_reg::<MCIBlkSiz>());
-        warn!("blkcnt: 0x{:x}", reg.read_reg::<MCIBytCnt>());
-        warn!("intmask: 0x{:x}", reg.read_reg::<MCIIntMask>());
-        warn!("cmdarg: 0x{:x}", reg.read_reg::<MCICmdArg>());
-        warn!("cmd: 0x{:x}", reg.read_reg::<MCICmd>());
-        warn!("resp0: 0x{:x}", reg.read_reg::<MCIResp0>());
-        warn!("reps1: 0x{:x}", reg.read_reg::<MCIResp1>());
-        warn!("resp2: 0x{:x}", reg.read_reg::<MCIResp2>());
-        warn!("resp3: 0x{:x}", reg.read_reg::<MCIResp3>());
-        warn!("maskints: 0x{:x}", reg.read_reg::<MCIMaskedInts>());
-        warn!("rawints: 0x{:x}", reg.read_reg::<MCIRawInts>());
-        warn!("status: 0x{:x}", reg.read_reg::<MCIStatus>());
-        warn!("fifoth: 0x{:x}", reg.read_reg::<MCIFifoTh>());
-        warn!("carddet: 0x{:x}", reg.read_reg::<MCICardDetect>());
-        warn!("wrtprt: 0x{:x}", reg.read_reg::<MCICardWrtp>());
-        warn!("cksts: 0x{:x}", reg.read_reg::<MCIClkSts>());
-        warn!("trans_cardcnt: 0x{:x}", reg.read_reg::<MCITranCardCnt>());
-        warn!("trans_fifocnt: 0x{:x}", reg.read_reg::<MCITranFifoCnt>());
-        warn!("debnce: 0x{:x}", reg.read_reg::<MCIDebnce>());
-        warn!("uid: 0x{:x}", reg.read_reg::<MCIUid>());
-        warn!("vid: 0x{:x}", reg.read_reg::<MCIVid>());
-        warn!("hwconf: 0x{:x}", reg.read_reg::<MCIHwconf>());
-        warn!("uhsreg: 0x{:x}", reg.read_reg::<MCIUhsReg>());
-        warn!("cardreset: 0x{:x}", reg.read_reg::<MCICardReset>());
-        warn!("busmode: 0x{:x}", reg.read_reg::<MCIBusMode>());
-        warn!("descaddrl: 0x{:x}", reg.read_reg::<MCIDescListAddrL>());
-        warn!("descaddrh: 0x{:x}", reg.read_reg::<MCIDescListAddrH>());
-        warn!("dmacstatus: 0x{:x}", reg.read_reg::<MCIDMACStatus>());
-        warn!("dmacinten: 0x{:x}", reg.read_reg::<MCIDMACIntEn>());
-        warn!("curdescaddrl: 0x{:x}", reg.read_reg::<MCICurDescAddrL>());
-        warn!("curdescaddrh: 0x{:x}", reg.read_reg::<MCIDescAddrH>());
-        warn!("curbufaddrl: 0x{:x}", reg.read_reg::<MCICurBufAddrL>());
-        warn!("curbufaddrh: 0x{:x}", reg.read_reg::<MCIBufAddrH>());
-        warn!("card_thrctl: 0x{:x}", reg.read_reg::<MCICardThrctl>());
-        warn!("clock_src: 0x{:x}", reg.read_reg::<MCIClkSrc>());
-        warn!("emmcddr: 0x{:x}", reg.read_reg::<MCIEmmcDdrReg>());
-        warn!("enableshift: 0x{:x}", reg.read_reg::<MCIEnableShift>());
+        warn!("register dump:\n{}", self.register_snapshot());
     }
 
     /// Dump command and data transfer information.
@@ -825,13 +910,9 @@ impl MCI {
         if reg_val.contains(busy_bits) {
             warn!("Card is busy, waiting ...");
         }
-        if let Err(err) = reg.retry_for(
-            |reg: MCIStatus| {
-                let result = !reg.contains(busy_bits);
-                MCI::relax_handler();
-                result
-            },
-            Some(RETRIES_TIMEOUT),
+        if let Err(err) = reg.poll_until(
+            |reg: MCIStatus| !reg.contains(busy_bits),
+            self.config.data_timeout(),
         ) {
             error!("Wait card busy timeout !!!");
             return Err(err);
@@ -844,12 +925,12 @@ impl MCI {
 
         reg.set_reg(MCICtrl::CONTROLLER_RESET);
 
-        reg.retry_for(
+        reg.poll_until(
             |reg_val: MCIStatus| {
                 reg.set_reg(MCICtrl::CONTROLLER_RESET);
                 !reg_val.contains(MCIStatus::DATA_BUSY)
             },
-            Some(RETRIES_TIMEOUT),
+            self.config.data_timeout(),
         )?;
 
         Ok(())
@@ -859,9 +940,9 @@ impl MCI {
         let reg = self.config.reg();
 
         /* wait command finish if previous command is in error state */
-        reg.retry_for(
+        reg.poll_until(
             |reg| (MCICmd::START & reg).bits() == 0,
-            Some(RETRIES_TIMEOUT),
+            self.config.cmd_timeout(),
         )?;
 
         /* update clock */
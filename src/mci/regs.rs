@@ -282,6 +282,8 @@ bitflags! {
         const ALL_BITS = 0x1FFFF;    /* RW All bits */
         const INTS_CMD_MASK = 0x1546;
         const INTS_DATA_MASK = 0x2288;
+        /* RE | RCRC | DCRC | RTO | DRTO | HTO | FRUN | HLE | SBE | EBE */
+        const INTS_ERR_MASK = 0xBFC2;
     }
 }
 
@@ -731,6 +733,17 @@ impl FlagReg for MCIDescListAddrH {
     const REG: u32 = FSDIF_DESC_LIST_ADDRH_OFFSET; // Assuming FSDIF_DESC_LIST_ADDRH_OFFSET is the corresponding register offset
 }
 
+// FSDIF_POLL_DEMAND_OFFSET Register
+bitflags! {
+    pub struct MCIPollDemand: u32 {
+        const PD = 1 << 0; /* WO Any value makes a suspended idma fetch the descriptor again */
+    }
+}
+
+impl FlagReg for MCIPollDemand {
+    const REG: u32 = FSDIF_POLL_DEMAND_OFFSET;
+}
+
 // FSDIF_DESC_LIST_ADDRL_OFFSET Register
 bitflags! {
     pub struct MCIDescListAddrL: u32 {
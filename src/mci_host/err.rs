@@ -1,6 +1,15 @@
 //! # MCI Host Error Types
 //!
 //! This module defines error types for MCI host controller operations.
+//!
+//! [`MCIHostError`] is the status used throughout the host layer. Block
+//! transfers return [`MCIHostTransferError`], which keeps the command, card
+//! status and controller interrupt status that led to the failure.
+
+use core::fmt;
+
+use super::constants::MCIHostCardStatusFlag;
+use crate::mci::MCIError;
 
 /// MCI host controller error enumeration.
 ///
@@ -120,6 +129,167 @@ pub enum MCIHostError {
     CardStatusBusy,
     /// Card initialization failed
     CardInitFailed,
+    /// Command response timed out
+    ResponseTimeout,
+    /// Command response CRC or format error
+    ResponseCrcError,
+    /// Data CRC, start bit or end bit error
+    DataCrcError,
+    /// Data read or host starvation timeout
+    DataTimeout,
+    /// Controller FIFO underrun/overrun
+    FifoError,
+    /// Internal DMA bus or descriptor error
+    DmaError,
+    /// Card was removed
+    NoCard,
+    /// Card reports fewer blocks written than requested
+    WrittenBlockCountMismatch,
+    /// Blocks read back differ from the ones written
+    WriteVerifyFailed,
+    /// Buffer allocation failed
+    NoMemory,
+    /// Card is suspended
+    Suspended,
+    /// A different card answered on resume
+    CardChanged,
+}
+
+impl From<MCIError> for MCIHostError {
+    fn from(err: MCIError) -> Self {
+        match err {
+            MCIError::Timeout | MCIError::TransTimeout | MCIError::CmdTimeout => {
+                MCIHostError::Timeout
+            }
+            MCIError::NotInit | MCIError::InvalidState => MCIHostError::HostNotReady,
+            MCIError::ShortBuf | MCIError::DmaBufUnalign => MCIHostError::InvalidArgument,
+            MCIError::NotSupport => MCIHostError::HostNotSupport,
+            MCIError::NoCard => MCIHostError::NoCard,
+            MCIError::Busy => MCIHostError::Busy,
+            MCIError::InvalidTiming => MCIHostError::SwitchBusTimingFailed,
+            MCIError::RespTimeout => MCIHostError::ResponseTimeout,
+            MCIError::RespCrc | MCIError::RespError => MCIHostError::ResponseCrcError,
+            MCIError::DataCrc | MCIError::StartBit | MCIError::EndBit => MCIHostError::DataCrcError,
+            MCIError::DataTimeout | MCIError::HostTimeout => MCIHostError::DataTimeout,
+            MCIError::Fifo => MCIHostError::FifoError,
+            MCIError::HardwareLocked => MCIHostError::Busy,
+            MCIError::DmaBusError
+            | MCIError::DmaDescUnavailable
+            | MCIError::DmaCardError
+            | MCIError::DmaDescOwned => MCIHostError::DmaError,
+        }
+    }
+}
+
+impl fmt::Display for MCIHostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
 }
 
+impl core::error::Error for MCIHostError {}
+
 pub type MCIHostStatus<T = ()> = Result<T, MCIHostError>;
+
+/// Failed transfer together with the context it failed in.
+///
+/// Fields the failing layer couldn't know about are left empty, e.g. a
+/// failure before any command was issued has no `cmd_index`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MCIHostTransferError {
+    /// Classified failure
+    pub kind: MCIHostError,
+    /// Index of the command that failed
+    pub cmd_index: Option<u32>,
+    /// Argument of the command that failed
+    pub argument: u32,
+    /// Error bits reported by the card in its R1 response
+    pub card_status: MCIHostCardStatusFlag,
+    /// Controller raw interrupt status when the failure was detected
+    pub raw_ints: u32,
+    /// First failing block, or the first block of the failed request if
+    /// the failure can't be narrowed down
+    pub lba: Option<u32>,
+    /// Number of blocks from `lba` the failure covers
+    pub block_count: Option<u32>,
+}
+
+impl MCIHostTransferError {
+    pub(crate) fn cmd_set(mut self, index: u32, argument: u32) -> Self {
+        self.cmd_index = Some(index);
+        self.argument = argument;
+        self
+    }
+
+    pub(crate) fn card_status_set(mut self, card_status: MCIHostCardStatusFlag) -> Self {
+        self.card_status = card_status;
+        self
+    }
+
+    pub(crate) fn raw_ints_set(mut self, raw_ints: u32) -> Self {
+        self.raw_ints = raw_ints;
+        self
+    }
+
+    /// Report the failure for `block_count` blocks from `lba`.
+    ///
+    /// Public so [`BlockDevice`](crate::sd::BlockDevice) implementations
+    /// can name the blocks they failed on.
+    pub fn blocks_set(mut self, lba: u32, block_count: u32) -> Self {
+        self.lba = Some(lba);
+        self.block_count = Some(block_count);
+        self
+    }
+
+    /// Report the failure for `block_count` blocks from `lba`, unless a
+    /// lower layer already named the blocks that failed.
+    pub fn blocks_set_if_unset(self, lba: u32, block_count: u32) -> Self {
+        if self.lba.is_some() {
+            return self;
+        }
+        self.blocks_set(lba, block_count)
+    }
+}
+
+impl From<MCIHostError> for MCIHostTransferError {
+    fn from(kind: MCIHostError) -> Self {
+        Self {
+            kind,
+            cmd_index: None,
+            argument: 0,
+            card_status: MCIHostCardStatusFlag::empty(),
+            raw_ints: 0,
+            lba: None,
+            block_count: None,
+        }
+    }
+}
+
+impl From<MCIHostTransferError> for MCIHostError {
+    fn from(err: MCIHostTransferError) -> Self {
+        err.kind
+    }
+}
+
+impl fmt::Display for MCIHostTransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(index) = self.cmd_index {
+            write!(f, ", CMD{} arg 0x{:08x}", index, self.argument)?;
+        }
+        if !self.card_status.is_empty() {
+            write!(f, ", card status {:?}", self.card_status)?;
+        }
+        if self.raw_ints != 0 {
+            write!(f, ", raw ints 0x{:x}", self.raw_ints)?;
+        }
+        if let (Some(lba), Some(count)) = (self.lba, self.block_count) {
+            write!(f, ", {} block(s) at LBA {}", count, lba)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for MCIHostTransferError {}
+
+pub type MCIHostTransferStatus<T = ()> = Result<T, MCIHostTransferError>;
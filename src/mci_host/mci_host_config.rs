@@ -58,7 +58,7 @@ impl MCIHostConfig {
             host_id: MCIId::MCI1,
             host_type: MCIHostType::SDIF,
             card_type: MCIHostCardType::MicroSD,
-            enable_irq: false, // TODO Will be set to true after IRQ-related features are implemented
+            enable_irq: false, // Set by SdCard::transfer_irq_set
             enable_dma: true,
             endian_mode: MCIHostEndianMode::Little,
             max_trans_size: SD_MAX_RW_BLK * SD_BLOCK_SIZE,
@@ -79,7 +79,7 @@ impl MCIHostConfig {
             host_id: MCIId::MCI0,
             host_type: MCIHostType::SDIF,
             card_type: MCIHostCardType::MicroSD,
-            enable_irq: false, // TODO Will be set to true after IRQ-related features are implemented
+            enable_irq: false, // Set by SdCard::transfer_irq_set
             enable_dma: false,
             endian_mode: MCIHostEndianMode::Little,
             max_trans_size: SD_MAX_RW_BLK * SD_BLOCK_SIZE,
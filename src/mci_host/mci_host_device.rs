@@ -4,10 +4,16 @@
 //! hardware device operations for SD/MMC card host controllers.
 
 use core::ptr::NonNull;
+use core::time::Duration;
 
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
+use crate::IoPad;
 use crate::mci::MCICmdData;
+#[cfg(feature = "cmd-trace")]
+use crate::mci::MCICmdTraceEntry;
+use crate::osa::OsaEvent;
 
 use super::MCIHost;
 use super::MCIHostCardIntFn;
@@ -44,6 +50,9 @@ pub(crate) trait MCIHostDevice {
     /// Deinitialize the device.
     fn deinit(&self);
 
+    /// Take back the I/O pad handed to the device.
+    fn iopad_take(&self) -> Option<IoPad>;
+
     /// Reset the device.
     fn reset(&self) -> MCIHostStatus;
 
@@ -142,6 +151,16 @@ pub(crate) trait MCIHostDevice {
     /// * `enable` - Whether to force clock on
     fn force_clock_on(&self, enable: bool);
 
+    /// Stop or restart the card clock while the bus is idle.
+    ///
+    /// The next command restarts a stopped clock by itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `gated` - Whether to stop the clock
+    /// * `host` - Reference to the host controller
+    fn clock_gate_set(&self, gated: bool, host: &MCIHost) -> MCIHostStatus;
+
     /// Enable or disable card interrupt.
     ///
     /// # Arguments
@@ -170,12 +189,12 @@ pub(crate) trait MCIHostDevice {
     /// # Arguments
     ///
     /// * `wait_card_status` - Status to wait for (inserted/removed)
-    /// * `timeout` - Timeout in milliseconds
+    /// * `timeout` - Maximum time to wait for the status
     /// * `host` - Reference to the host controller
     fn card_detect_status_polling(
         &self,
         wait_card_status: SDStatus,
-        timeout: u32,
+        timeout: Duration,
         host: &MCIHost,
     ) -> MCIHostStatus;
 
@@ -236,6 +255,71 @@ pub(crate) trait MCIHostDevice {
     /// * `host` - Reference to the host controller
     fn transfer_function(&self, content: &mut MCIHostTransfer, host: &MCIHost) -> MCIHostStatus;
 
+    /* streaming transfer related functions */
+    /// Start an open-ended read or write over a ring of DMA buffers.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The CMD18/CMD25 to send, without data; gets the response
+    /// * `write` - Whether the card is written
+    /// * `chunks` - Bus addresses of the ring buffers, `chunk_len` bytes each
+    /// * `chunk_len` - Length of a buffer in bytes, whole blocks
+    /// * `armed` - Number of leading buffers the DMA may use right away
+    /// * `host` - Reference to the host controller
+    #[cfg(feature = "dma")]
+    fn stream_start(
+        &self,
+        content: &mut MCIHostTransfer,
+        write: bool,
+        chunks: &[usize],
+        chunk_len: u32,
+        armed: usize,
+        host: &MCIHost,
+    ) -> MCIHostStatus;
+
+    /// Whether the DMA is done with buffer `chunk` of the running stream.
+    #[cfg(feature = "dma")]
+    fn stream_chunk_done(&self, chunk: usize) -> MCIHostStatus<bool>;
+
+    /// Give buffer `chunk` of the running stream back to the DMA.
+    #[cfg(feature = "dma")]
+    fn stream_chunk_arm(&self, chunk: usize) -> MCIHostStatus;
+
+    /// End the running stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The CMD12 to send; gets the response
+    /// * `write` - Whether the stream writes the card
+    /// * `host` - Reference to the host controller
+    #[cfg(feature = "dma")]
+    fn stream_stop(
+        &self,
+        content: &mut MCIHostTransfer,
+        write: bool,
+        host: &MCIHost,
+    ) -> MCIHostStatus;
+
+    /// Wait for transfers on `event`, notified by the interrupt handler,
+    /// instead of polling the controller. `None` polls again.
+    fn irq_event_set(&self, event: Option<Arc<OsaEvent>>);
+
+    /// Set how long the controller waits for commands and data transfers.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd_timeout` - Maximum time to wait for a command to complete
+    /// * `data_timeout` - Maximum time to wait for a data transfer or a busy card
+    fn timeouts_set(&self, cmd_timeout: Duration, data_timeout: Duration);
+
+    /// Log the commands recorded by the controller's command trace.
+    #[cfg(feature = "cmd-trace")]
+    fn cmd_trace_dump(&self);
+
+    /// Remove and return the commands recorded by the controller's command trace, oldest first.
+    #[cfg(feature = "cmd-trace")]
+    fn cmd_trace_drain(&self) -> Vec<MCICmdTraceEntry>;
+
     /* boot related functions */
     // TODO: These will never be used
     // fn start_boot(&self, host_config: &MCIHostBootConfig, cmd: &MCIHostCmd, buffer: &mut [u8]) -> MCIHostStatus;
@@ -0,0 +1,185 @@
+//! # Transfer Statistics
+//!
+//! Counters and latency histograms collected by the host while it talks to a
+//! card, read through [`SdCard::stats`](super::sd::SdCard::stats).
+
+use core::fmt;
+use core::time::Duration;
+
+use super::err::MCIHostError;
+
+/* upper bounds of the latency histogram buckets, the last bucket is unbounded */
+const LATENCY_BUCKET_BOUNDS: [Duration; LATENCY_BUCKETS - 1] = [
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+];
+
+/// Number of buckets of a [`LatencyHistogram`].
+pub const LATENCY_BUCKETS: usize = 12;
+
+/// Histogram of request latencies with fixed buckets from 100us to 250ms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    pub(crate) fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BUCKET_BOUNDS
+            .iter()
+            .position(|bound| latency < *bound)
+            .unwrap_or(LATENCY_BUCKETS - 1);
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total = self.total.saturating_add(latency);
+        self.max = self.max.max(latency);
+    }
+
+    /// Number of requests in each bucket.
+    pub fn buckets(&self) -> &[u64; LATENCY_BUCKETS] {
+        &self.buckets
+    }
+
+    /// Exclusive upper bound of bucket `index`, `None` for the last bucket.
+    pub fn bucket_bound(index: usize) -> Option<Duration> {
+        LATENCY_BUCKET_BOUNDS.get(index).copied()
+    }
+
+    /// Number of recorded requests.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Mean latency, zero if nothing was recorded.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+    }
+
+    /// Longest recorded latency.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+}
+
+impl fmt::Display for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} request(s), mean {}us, max {}us",
+            self.count,
+            self.mean().as_micros(),
+            self.max.as_micros()
+        )?;
+        for (index, count) in self.buckets.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            match Self::bucket_bound(index) {
+                Some(bound) => write!(f, ", <{}us: {}", bound.as_micros(), count)?,
+                None => write!(f, ", more: {}", count)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counters of the commands and transfers issued to a card.
+///
+/// Counting starts when the card is created and restarts on
+/// [`SdCard::stats_reset`](super::sd::SdCard::stats_reset).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MCIHostStats {
+    /// Commands sent to the card, including retries
+    pub commands: u64,
+    /// Blocks read successfully
+    pub blocks_read: u64,
+    /// Blocks written successfully
+    pub blocks_written: u64,
+    /// Bytes read successfully
+    pub bytes_read: u64,
+    /// Bytes written successfully
+    pub bytes_written: u64,
+    /// Commands retried after an error
+    pub retries: u64,
+    /// Sampling point retunes
+    pub retunes: u64,
+    /// CMD12 sent to abort a failed data transfer
+    pub aborts: u64,
+    /// Time spent waiting for the card to leave the busy state
+    pub busy_wait: Duration,
+    /// Response CRC errors
+    pub response_crc_errors: u64,
+    /// Data CRC errors
+    pub data_crc_errors: u64,
+    /// Response timeouts
+    pub response_timeouts: u64,
+    /// Data timeouts
+    pub data_timeouts: u64,
+    /// Timeouts waiting for the controller
+    pub host_timeouts: u64,
+    /// Other failed commands
+    pub other_errors: u64,
+    /// Latency of read requests
+    pub read_latency: LatencyHistogram,
+    /// Latency of write requests
+    pub write_latency: LatencyHistogram,
+}
+
+impl MCIHostStats {
+    pub(crate) fn error_record(&mut self, err: &MCIHostError) {
+        match err {
+            MCIHostError::ResponseCrcError => self.response_crc_errors += 1,
+            MCIHostError::DataCrcError => self.data_crc_errors += 1,
+            MCIHostError::ResponseTimeout => self.response_timeouts += 1,
+            MCIHostError::DataTimeout => self.data_timeouts += 1,
+            MCIHostError::Timeout => self.host_timeouts += 1,
+            _ => self.other_errors += 1,
+        }
+    }
+}
+
+impl fmt::Display for MCIHostStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "commands {}, retries {}, retunes {}, aborts {}, busy wait {}us",
+            self.commands,
+            self.retries,
+            self.retunes,
+            self.aborts,
+            self.busy_wait.as_micros()
+        )?;
+        writeln!(
+            f,
+            "read {} block(s) / {} bytes, written {} block(s) / {} bytes",
+            self.blocks_read, self.bytes_read, self.blocks_written, self.bytes_written
+        )?;
+        writeln!(
+            f,
+            "errors: response crc {}, data crc {}, response timeout {}, data timeout {}, host timeout {}, other {}",
+            self.response_crc_errors,
+            self.data_crc_errors,
+            self.response_timeouts,
+            self.data_timeouts,
+            self.host_timeouts,
+            self.other_errors
+        )?;
+        writeln!(f, "read latency: {}", self.read_latency)?;
+        write!(f, "write latency: {}", self.write_latency)
+    }
+}
@@ -5,11 +5,15 @@
 use alloc::vec::Vec;
 
 use super::constants::*;
+#[cfg(feature = "dma")]
+use crate::mci::MCIDmaSegment;
 
 /// Host transfer structure containing command and data.
 pub(crate) struct MCIHostTransfer {
     data: Option<MCIHostData>,
     cmd: Option<MCIHostCmd>,
+    /// Controller raw interrupt status of the last failed attempt
+    raw_ints: u32,
 }
 
 impl MCIHostTransfer {
@@ -18,9 +22,20 @@ impl MCIHostTransfer {
         MCIHostTransfer {
             data: None,
             cmd: None,
+            raw_ints: 0,
         }
     }
 
+    /// Get the raw interrupt status latched by the last failed attempt.
+    pub(crate) fn raw_ints(&self) -> u32 {
+        self.raw_ints
+    }
+
+    /// Latch the raw interrupt status of a failed attempt.
+    pub(crate) fn raw_ints_set(&mut self, raw_ints: u32) {
+        self.raw_ints = raw_ints
+    }
+
     /// Get the data portion of the transfer.
     pub(crate) fn data(&self) -> Option<&MCIHostData> {
         self.data.as_ref()
@@ -73,6 +88,12 @@ pub(crate) struct MCIHostData {
     rx_data: Option<Vec<u32>>,
     /// Buffer for data to send
     tx_data: Option<Vec<u32>>,
+    /// Scatter list to receive into, instead of `rx_data`
+    #[cfg(feature = "dma")]
+    rx_segments: Option<Vec<MCIDmaSegment>>,
+    /// Scatter list to send from, instead of `tx_data`
+    #[cfg(feature = "dma")]
+    tx_segments: Option<Vec<MCIDmaSegment>>,
 }
 
 #[allow(unused)]
@@ -88,6 +109,10 @@ impl MCIHostData {
             block_count: 0,
             rx_data: None,
             tx_data: None,
+            #[cfg(feature = "dma")]
+            rx_segments: None,
+            #[cfg(feature = "dma")]
+            tx_segments: None,
         }
     }
 
@@ -107,6 +132,10 @@ impl MCIHostData {
         self.enable_auto_command23
     }
 
+    pub(crate) fn enable_auto_command23_set(&mut self, enable_auto_command23: bool) {
+        self.enable_auto_command23 = enable_auto_command23
+    }
+
     pub(crate) fn enable_ignore_error(&self) -> bool {
         self.enable_ignore_error
     }
@@ -162,6 +191,26 @@ impl MCIHostData {
     pub(crate) fn tx_data_take(&mut self) -> Option<Vec<u32>> {
         self.tx_data.take()
     }
+
+    #[cfg(feature = "dma")]
+    pub(crate) fn rx_segments(&self) -> Option<&Vec<MCIDmaSegment>> {
+        self.rx_segments.as_ref()
+    }
+
+    #[cfg(feature = "dma")]
+    pub(crate) fn rx_segments_set(&mut self, rx_segments: Option<Vec<MCIDmaSegment>>) {
+        self.rx_segments = rx_segments
+    }
+
+    #[cfg(feature = "dma")]
+    pub(crate) fn tx_segments(&self) -> Option<&Vec<MCIDmaSegment>> {
+        self.tx_segments.as_ref()
+    }
+
+    #[cfg(feature = "dma")]
+    pub(crate) fn tx_segments_set(&mut self, tx_segments: Option<Vec<MCIDmaSegment>>) {
+        self.tx_segments = tx_segments
+    }
 }
 
 /// Host command structure.
@@ -3,17 +3,20 @@ use core::mem::take;
 use core::ptr::NonNull;
 use core::time::Duration;
 
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 #[cfg(feature = "dma")]
-use dma_api::DSlice;
+use dma_api::{DVec, Direction};
 use log::*;
 
 use super::MCIHost;
 use super::constants::SDStatus;
+#[cfg(feature = "cmd-trace")]
+use crate::mci::MCICmdTraceEntry;
 use crate::mci::constants::*;
 use crate::mci::mci_data::MCIData;
 use crate::mci::regs::MCIIntMask;
-use crate::mci::{MCI, MCICmdData, MCIConfig};
+use crate::mci::{MCI, MCICmdData, MCIConfig, MCIResult};
 use crate::mci_host::MCIHostCardIntFn;
 use crate::mci_host::constants::*;
 use crate::mci_host::err::*;
@@ -22,52 +25,161 @@ use crate::mci_host::mci_host_config::*;
 use crate::mci_host::mci_host_device::MCIHostDevice;
 use crate::mci_host::mci_host_transfer::MCIHostTransfer;
 use crate::mci_host::sd::constants::SdCmd;
-use crate::tools::swap_half_word_byte_sequence_u32;
+use crate::osa::OsaEvent;
+use crate::tools::{Deadline, swap_half_word_byte_sequence_u32};
 use crate::{IoPad, sleep};
 
 #[cfg(feature = "dma")]
-use crate::mci::mci_dma::FSdifIDmaDesc;
+use crate::mci::mci_dma::{FSdifIDmaDesc, MCIDmaBuf};
+#[cfg(feature = "dma")]
+use crate::mci_host::sd::constants::SD_BLOCK_SIZE;
 
 pub(crate) struct SDIFDev {
     hc: RefCell<MCI>,           // SDIF hardware controller
     hc_cfg: RefCell<MCIConfig>, // SDIF configuration
-    #[cfg(feature = "dma")]
-    rw_desc: PoolBuffer, // DMA descriptor pointer, for managing data transfer TODO: Consider using vec or DVec directly
-    desc_num: Cell<u32>, // Descriptor count, representing the number of DMA descriptors
+    desc_num: Cell<u32>,        // Descriptor count, representing the number of DMA descriptors
 }
 
 impl SDIFDev {
     pub fn new(addr: NonNull<u8>, desc_num: usize) -> Self {
-        #[cfg(feature = "dma")]
-        {
-            let align = SD_BLOCK_SIZE;
-            let length = core::mem::size_of::<FSdifIDmaDesc>() * desc_num;
-            let rw_desc = match osa_alloc_aligned(length, align) {
-                Err(e) => {
-                    error!("alloc internal buffer failed! err: {:?}", e);
-                    panic!("Failed to allocate internal buffer");
-                }
-                Ok(buffer) => buffer,
-            };
-
-            Self {
-                hc: MCI::new(MCIConfig::new(addr)).into(),
-                hc_cfg: MCIConfig::new(addr).into(),
-                rw_desc,
-                desc_num: (desc_num as u32).into(),
-            }
-        }
-
-        #[cfg(feature = "pio")]
         Self {
             hc: MCI::new(MCIConfig::new(addr)).into(),
             hc_cfg: MCIConfig::new(addr).into(),
             desc_num: (desc_num as u32).into(),
         }
     }
+
+    /// The descriptor list of the controller, allocated mapped for the IDMAC
+    /// the first time.
+    ///
+    /// `dma_api` has no coherent memory, the list is cached and kept
+    /// consistent one descriptor, a whole cache line, at a time.
+    #[cfg(feature = "dma")]
+    fn idma_list_get(&self) -> MCIHostStatus<DVec<FSdifIDmaDesc>> {
+        let desc_num = self.desc_num.get() as usize;
+        if let Some(descs) = self.hc.borrow_mut().idma_list_take()
+            && descs.len() == desc_num
+        {
+            return Ok(descs);
+        }
+        DVec::zeros(desc_num, SD_BLOCK_SIZE, Direction::Bidirectional).ok_or_else(|| {
+            error!("alloc {} dma descriptors failed!", desc_num);
+            MCIHostError::Fail
+        })
+    }
     pub fn iopad_set(&self, iopad: IoPad) {
         self.hc.borrow_mut().iopad_set(iopad);
     }
+
+    /// Restart the clock if it was gated while idle and note the activity.
+    fn clock_wake(&self, host: &MCIHost) -> MCIHostStatus {
+        self.clock_gate_set(false, host)?;
+        host.last_active.set(crate::now());
+        Ok(())
+    }
+
+    /// Issue the command, wait for it to complete and fetch the response.
+    fn cmd_data_transfer(&self, cmd_data: &mut MCICmdData, host: &MCIHost) -> MCIResult {
+        #[cfg(feature = "cmd-trace")]
+        let start = crate::now();
+        let result = self.cmd_data_transfer_once(cmd_data, host);
+        #[cfg(feature = "cmd-trace")]
+        self.hc.borrow_mut().cmd_trace_mut().record(
+            cmd_data,
+            crate::now().saturating_sub(start),
+            result,
+        );
+        result
+    }
+
+    /// Map the data buffer of `cmd_data` for the IDMAC, for reads or writes
+    /// as the command says.
+    #[cfg(feature = "dma")]
+    fn dma_buf_map(cmd_data: &mut MCICmdData) -> Option<MCIDmaBuf> {
+        let direction = if cmd_data.flag().contains(MCICmdFlag::READ_DATA) {
+            Direction::FromDevice
+        } else {
+            Direction::ToDevice
+        };
+        let data = cmd_data.get_mut_data()?;
+        let dma_buf = match MCIDmaBuf::map(data.buf_take()?, direction) {
+            Ok(dma_buf) => dma_buf,
+            Err(buf) => {
+                /* the transfer fails without a mapped buffer, the data stays */
+                data.buf_set(Some(buf));
+                return None;
+            }
+        };
+        data.buf_dma_set(dma_buf.bus_addr());
+        debug!(
+            "buf PA: 0x{:x}, blksz: {}, datalen: {}",
+            data.buf_dma(),
+            data.blksz(),
+            data.datalen()
+        );
+        Some(dma_buf)
+    }
+
+    /// Hand the response of a stream command back in `content`, or its error.
+    #[cfg(feature = "dma")]
+    fn stream_cmd_done(
+        &self,
+        content: &mut MCIHostTransfer,
+        cmd_data: &MCICmdData,
+        result: MCIResult,
+        host: &MCIHost,
+    ) -> MCIHostStatus {
+        if let Err(err) = result {
+            content.raw_ints_set(cmd_data.raw_ints());
+            let err = err.into();
+            host.stats.borrow_mut().error_record(&err);
+            return Err(err);
+        }
+
+        if let Some(cmd) = content.cmd_mut()
+            && cmd.response_type() != MCIHostResponseType::None
+        {
+            cmd.response_mut()
+                .copy_from_slice(&cmd_data.get_response()[..4]);
+        }
+        Ok(())
+    }
+
+    /* the transfer itself, without recording it in the command trace */
+    fn cmd_data_transfer_once(&self, cmd_data: &mut MCICmdData, host: &MCIHost) -> MCIResult {
+        /* in interrupt mode the waits below sleep on the event, drop a stale notification */
+        host.event.clear();
+        if host.config.enable_dma {
+            #[cfg(feature = "dma")]
+            self.hc.borrow_mut().dma_transfer(cmd_data)?;
+            #[cfg(feature = "dma")]
+            self.hc.borrow_mut().poll_wait_dma_end(cmd_data)?;
+        } else {
+            #[cfg(feature = "pio")]
+            self.hc.borrow_mut().pio_transfer(cmd_data)?;
+            #[cfg(feature = "pio")]
+            self.hc.borrow_mut().poll_wait_pio_end(cmd_data)?;
+        }
+
+        debug!(
+            "Transfer cmd: {}, arg: 0x{:x}, flag: {:?}",
+            cmd_data.cmdidx(),
+            cmd_data.cmdarg(),
+            cmd_data.flag()
+        );
+
+        if let Err(err) = self.hc.borrow_mut().cmd_response_get(cmd_data) {
+            info!("Transfer cmd and data failed !!!");
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SDIFDev {
+    fn drop(&mut self) {
+        self.deinit();
+    }
 }
 
 impl MCIHostDevice for SDIFDev {
@@ -78,15 +190,31 @@ impl MCIHostDevice for SDIFDev {
     }
 
     fn do_init(&self, addr: NonNull<u8>, host: &MCIHost) -> MCIHostStatus {
-        let mci_config = MCIConfig::lookup_config(addr);
+        let mut mci_config = MCIConfig::lookup_config(addr);
+        /* keep timeouts configured before a re-init */
+        mci_config.cmd_timeout_set(self.hc_cfg.borrow().cmd_timeout());
+        mci_config.data_timeout_set(self.hc_cfg.borrow().data_timeout());
         let iopad = self
             .hc
             .borrow_mut()
             .iopad_take()
             .ok_or(MCIHostError::NoData)?;
 
+        /* keep the descriptors and the command history across the re-init */
+        #[cfg(feature = "dma")]
+        let descs = if host.config.enable_dma {
+            Some(self.idma_list_get()?)
+        } else {
+            None
+        };
+        #[cfg(feature = "cmd-trace")]
+        let cmd_trace = core::mem::take(self.hc.borrow_mut().cmd_trace_mut());
         *self.hc.borrow_mut() = MCI::new(MCIConfig::lookup_config(addr));
         self.hc.borrow_mut().iopad_set(iopad);
+        #[cfg(feature = "cmd-trace")]
+        {
+            *self.hc.borrow_mut().cmd_trace_mut() = cmd_trace;
+        }
 
         // Force restart
         let restart_mci = MCI::new_restart(MCIConfig::restart(addr));
@@ -100,19 +228,15 @@ impl MCIHostDevice for SDIFDev {
         }
 
         if host.config.enable_irq {
-            // TODO
+            self.hc.borrow_mut().irq_event_set(Some(host.event.clone()));
         }
 
         #[cfg(feature = "dma")]
-        if host.config.enable_dma {
-            if let Err(_) = self
-                .hc
-                .borrow_mut()
-                .set_idma_list(&self.rw_desc, self.desc_num.get())
-            {
-                error!("idma list set failed!");
-                return Err(MCIHostError::Fail);
-            }
+        if let Some(descs) = descs
+            && self.hc.borrow_mut().set_idma_list(descs).is_err()
+        {
+            error!("idma list set failed!");
+            return Err(MCIHostError::Fail);
         }
 
         *self.hc_cfg.borrow_mut() = mci_config;
@@ -121,10 +245,23 @@ impl MCIHostDevice for SDIFDev {
 
     fn deinit(&self) {
         // TODO FSDIFHOST_RevokeIrq
-        let _ = self.hc.borrow_mut().config_deinit();
+        let mut hc = self.hc.borrow_mut();
+        if !hc.is_ready() {
+            return;
+        }
+        if let Err(err) = hc.config_deinit() {
+            warn!("Sdio ctrl stop transfer failed: {:?}", err);
+        }
+        /* the IDMAC is stopped, its descriptors can go */
+        #[cfg(feature = "dma")]
+        drop(hc.idma_list_take());
         info!("Sdio ctrl deinited !!!")
     }
 
+    fn iopad_take(&self) -> Option<IoPad> {
+        self.hc.borrow_mut().iopad_take()
+    }
+
     fn reset(&self) -> MCIHostStatus {
         match self.hc.borrow_mut().restart() {
             Ok(_) => Ok(()),
@@ -209,7 +346,13 @@ impl MCIHostDevice for SDIFDev {
         Ok(())
     }
 
-    fn card_detect_init(&self, _cd: &MCIHostCardDetect) -> MCIHostStatus {
+    fn card_detect_init(&self, cd: &MCIHostCardDetect) -> MCIHostStatus {
+        /* the controller raises the card-detect interrupt on both edges */
+        self.hc.borrow().interrupt_mask_set(
+            MCIIntrType::GeneralIntr,
+            MCIIntMask::CD_BIT.bits(),
+            cd.typ == MCIHostDetectCardType::ByHostCD,
+        );
         Ok(())
     }
 
@@ -250,12 +393,12 @@ impl MCIHostDevice for SDIFDev {
     fn card_detect_status_polling(
         &self,
         wait_card_status: SDStatus,
-        _timeout: u32,
+        timeout: Duration,
         host: &MCIHost,
     ) -> MCIHostStatus {
         let cd = host.cd.as_ref().ok_or(MCIHostError::NoData)?;
 
-        let mut retry_times: usize = 100;
+        let deadline = Deadline::after(timeout);
 
         /* Wait card inserted. */
         loop {
@@ -269,12 +412,10 @@ impl MCIHostDevice for SDIFDev {
                 break;
             }
 
-            if retry_times == 0 {
+            if deadline.expired() {
                 info!("Wait card insert timeout !!!");
                 return Err(MCIHostError::Timeout);
             }
-
-            retry_times -= 1;
         }
         Ok(())
     }
@@ -299,6 +440,7 @@ impl MCIHostDevice for SDIFDev {
             info!("BUS CLOCK: {}", target_clock);
             // Update instance clock frequency
             host.curr_clock_freq.set(target_clock);
+            host.clock_gated.set(false);
         } else {
             info!("Failed to update clock");
         }
@@ -310,6 +452,16 @@ impl MCIHostDevice for SDIFDev {
         self.hc.borrow().clock_set(enable);
     }
 
+    fn clock_gate_set(&self, gated: bool, host: &MCIHost) -> MCIHostStatus {
+        if host.clock_gated.get() == gated {
+            return Ok(());
+        }
+        self.hc.borrow().clock_gate_set(gated)?;
+        host.clock_gated.set(gated);
+        debug!("Card clock {}", if gated { "gated" } else { "running" });
+        Ok(())
+    }
+
     fn card_is_busy(&self) -> bool {
         self.hc.borrow().check_if_card_busy()
     }
@@ -325,8 +477,10 @@ impl MCIHostDevice for SDIFDev {
             None => return Ok(()),
         };
 
-        if cmd.index() == MCIHostCommonCmd::ReadMultipleBlock as u32
-            || cmd.index() == MCIHostCommonCmd::WriteMultipleBlock as u32
+        /* CMD23 tells the card where the data ends, no CMD12 after it */
+        if data.enable_auto_command23()
+            && (cmd.index() == MCIHostCommonCmd::ReadMultipleBlock as u32
+                || cmd.index() == MCIHostCommonCmd::WriteMultipleBlock as u32)
         {
             let block_count = data.block_count();
 
@@ -383,42 +537,46 @@ impl MCIHostDevice for SDIFDev {
             let mut out_data = MCIData::new();
 
             flag |= MCICmdFlag::EXP_DATA;
+            if in_data.enable_auto_command12() {
+                flag |= MCICmdFlag::NEED_AUTO_STOP;
+            }
+
+            out_data.blksz_set(in_data.block_size() as u32);
+            out_data.blkcnt_set(in_data.block_count());
+            out_data.datalen_set(in_data.block_size() as u32 * in_data.block_count());
+
+            /* the caller's pages go to the descriptors as they are, no buffer in between */
+            #[cfg(feature = "dma")]
+            if let Some(segments) = in_data.rx_segments() {
+                flag |= MCICmdFlag::READ_DATA;
+                out_data.segments_set(segments.clone());
+            } else if let Some(segments) = in_data.tx_segments() {
+                flag |= MCICmdFlag::WRITE_DATA;
+                out_data.segments_set(segments.clone());
+            }
+            #[cfg(feature = "dma")]
+            let scatter = !out_data.segments().is_empty();
+            #[cfg(not(feature = "dma"))]
+            let scatter = false;
 
-            let buf = if let Some(rx_data) = in_data.rx_data_mut() {
+            let buf = if scatter {
+                None
+            } else if let Some(rx_data) = in_data.rx_data_mut() {
                 // Handle receive data
                 flag |= MCICmdFlag::READ_DATA;
                 // TODO The CLONE here will reduce driver speed, need to solve this performance issue - taking it out directly might be better
                 // rx_data.clone()
-                take(rx_data)
+                Some(take(rx_data))
             } else if let Some(tx_data) = in_data.tx_data_mut() {
                 // Handle transmit data
                 flag |= MCICmdFlag::WRITE_DATA;
-                take(tx_data)
+                Some(take(tx_data))
             } else {
                 // Neither rx_data nor tx_data is available
                 panic!("Transaction data initialized but contains neither rx_data nor tx_data");
             };
 
-            out_data.blksz_set(in_data.block_size() as u32);
-            out_data.blkcnt_set(in_data.block_count());
-            out_data.datalen_set(in_data.block_size() as u32 * in_data.block_count());
-
-            #[cfg(feature = "dma")]
-            {
-                let slice = DSlice::from(&buf[..]);
-                out_data.buf_dma_set(slice.bus_addr() as usize);
-                drop(slice);
-            }
-
-            out_data.buf_set(Some(buf));
-
-            #[cfg(feature = "dma")]
-            debug!(
-                "buf PA: 0x{:x}, blksz: {}, datalen: {}",
-                out_data.buf_dma(),
-                out_data.blksz(),
-                out_data.datalen()
-            );
+            out_data.buf_set(buf);
 
             Some(out_data)
         } else {
@@ -442,64 +600,39 @@ impl MCIHostDevice for SDIFDev {
     fn transfer_function(&self, content: &mut MCIHostTransfer, host: &MCIHost) -> MCIHostStatus {
         self.pre_command(content, host)?;
 
+        /* pre_command may issue CMD23 itself, so lock after it */
+        let _guard = host.lock.lock();
+        self.clock_wake(host)?;
         let mut cmd_data = self.covert_command_info(content);
+        host.stats.borrow_mut().commands += 1;
 
-        if host.config.enable_dma {
-            #[cfg(feature = "dma")]
-            if let Err(_) = self.hc.borrow_mut().dma_transfer(&mut cmd_data) {
-                return Err(MCIHostError::NoData);
-            }
-            #[cfg(feature = "dma")]
-            if let Err(_) = self.hc.borrow_mut().poll_wait_dma_end(&mut cmd_data) {
-                return Err(MCIHostError::NoData);
-            }
+        /* the buffer stays mapped for the device until the transfer is over */
+        #[cfg(feature = "dma")]
+        let dma_buf = if host.config.enable_dma {
+            Self::dma_buf_map(&mut cmd_data)
         } else {
-            #[cfg(feature = "pio")]
-            if self.hc.borrow_mut().pio_transfer(&mut cmd_data).is_err() {
-                return Err(MCIHostError::NoData);
-            }
-            #[cfg(feature = "pio")]
-            if self
-                .hc
-                .borrow_mut()
-                .poll_wait_pio_end(&mut cmd_data)
-                .is_err()
-            {
-                return Err(MCIHostError::NoData);
-            }
+            None
+        };
+        let result = self.cmd_data_transfer(&mut cmd_data, host);
+        #[cfg(feature = "dma")]
+        if let Some(dma_buf) = dma_buf
+            && let Some(data) = cmd_data.get_mut_data()
+        {
+            data.buf_set(Some(dma_buf.unmap()));
         }
 
-        debug!(
-            "Transfer cmd: {}, arg: 0x{:x}, flag: {:?}",
-            cmd_data.cmdidx(),
-            cmd_data.cmdarg(),
-            cmd_data.flag()
-        );
-
-        if self
-            .hc
-            .borrow_mut()
-            .cmd_response_get(&mut cmd_data)
-            .is_err()
-        {
-            info!("Transfer cmd and data failed !!!");
-            return Err(MCIHostError::Timeout);
+        if let Err(err) = result {
+            content.raw_ints_set(cmd_data.raw_ints());
+            let err = err.into();
+            host.stats.borrow_mut().error_record(&err);
+            return Err(err);
         }
 
         // TODO: The `CLONE` here will reduce driver speed, need to solve this performance issue - taking it out directly might be better
-        if content.data().is_some() {
-            let data = cmd_data.get_data().unwrap();
-            unsafe {
-                invalidate(
-                    data.buf().unwrap().as_ptr() as *const u8,
-                    data.buf().unwrap().len() * 4,
-                );
-            }
-            if let Some(rx_data) = data.buf()
-                && let Some(in_data) = content.data_mut()
-            {
-                in_data.rx_data_set(Some(rx_data.clone()));
-            }
+        if let Some(rx_data) = cmd_data.get_data().and_then(|data| data.buf())
+            && let Some(in_data) = content.data_mut()
+        {
+            in_data.rx_data_set(Some(rx_data.clone()));
         }
 
         if let Some(cmd) = content.cmd_mut()
@@ -513,4 +646,100 @@ impl MCIHostDevice for SDIFDev {
 
         Ok(())
     }
+
+    #[cfg(feature = "dma")]
+    fn stream_start(
+        &self,
+        content: &mut MCIHostTransfer,
+        write: bool,
+        chunks: &[usize],
+        chunk_len: u32,
+        armed: usize,
+        host: &MCIHost,
+    ) -> MCIHostStatus {
+        let _guard = host.lock.lock();
+        self.clock_wake(host)?;
+        let mut cmd_data = self.covert_command_info(content);
+        *cmd_data.flag_mut() |= MCICmdFlag::EXP_DATA
+            | if write {
+                MCICmdFlag::WRITE_DATA
+            } else {
+                MCICmdFlag::READ_DATA
+            };
+        host.stats.borrow_mut().commands += 1;
+
+        #[cfg(feature = "cmd-trace")]
+        let start = crate::now();
+        let result = self.hc.borrow_mut().stream_start(
+            &mut cmd_data,
+            chunks,
+            chunk_len,
+            host.config.def_block_size as u32,
+            armed,
+        );
+        #[cfg(feature = "cmd-trace")]
+        self.hc.borrow_mut().cmd_trace_mut().record(
+            &cmd_data,
+            crate::now().saturating_sub(start),
+            result,
+        );
+        self.stream_cmd_done(content, &cmd_data, result, host)
+    }
+
+    #[cfg(feature = "dma")]
+    fn stream_chunk_done(&self, chunk: usize) -> MCIHostStatus<bool> {
+        Ok(self.hc.borrow().stream_chunk_done(chunk)?)
+    }
+
+    #[cfg(feature = "dma")]
+    fn stream_chunk_arm(&self, chunk: usize) -> MCIHostStatus {
+        Ok(self.hc.borrow_mut().stream_chunk_arm(chunk)?)
+    }
+
+    #[cfg(feature = "dma")]
+    fn stream_stop(
+        &self,
+        content: &mut MCIHostTransfer,
+        write: bool,
+        host: &MCIHost,
+    ) -> MCIHostStatus {
+        let _guard = host.lock.lock();
+        self.clock_wake(host)?;
+        let mut cmd_data = self.covert_command_info(content);
+        /* cut the data short rather than wait for it to end */
+        *cmd_data.flag_mut() |= MCICmdFlag::ABORT;
+        host.stats.borrow_mut().commands += 1;
+
+        #[cfg(feature = "cmd-trace")]
+        let start = crate::now();
+        let result = self.hc.borrow_mut().stream_stop(&mut cmd_data, write);
+        #[cfg(feature = "cmd-trace")]
+        self.hc.borrow_mut().cmd_trace_mut().record(
+            &cmd_data,
+            crate::now().saturating_sub(start),
+            result,
+        );
+        self.stream_cmd_done(content, &cmd_data, result, host)
+    }
+
+    fn irq_event_set(&self, event: Option<Arc<OsaEvent>>) {
+        self.hc.borrow_mut().irq_event_set(event);
+    }
+
+    fn timeouts_set(&self, cmd_timeout: Duration, data_timeout: Duration) {
+        let mut hc_cfg = self.hc_cfg.borrow_mut();
+        hc_cfg.cmd_timeout_set(cmd_timeout);
+        hc_cfg.data_timeout_set(data_timeout);
+        self.hc.borrow_mut().timeouts_set(cmd_timeout, data_timeout);
+    }
+
+    #[cfg(feature = "cmd-trace")]
+    fn cmd_trace_dump(&self) {
+        self.hc.borrow().cmd_trace().dump();
+    }
+
+    #[cfg(feature = "cmd-trace")]
+    fn cmd_trace_drain(&self) -> Vec<MCICmdTraceEntry> {
+        self.hc.borrow_mut().cmd_trace_mut().drain().collect()
+    }
 }
@@ -6,6 +6,7 @@
 //! ## Components
 //!
 //! - **MCIHost**: Main host controller structure managing card operations
+//! - **MCIHostStats**: Transfer counters and latency histograms
 //! - **mci_sdif**: SDIF device implementation
 //! - **sd**: SD card specific operations and data structures
 //!
@@ -24,14 +25,18 @@ mod mci_card_base;
 mod mci_host_card_detect;
 mod mci_host_config;
 mod mci_host_device;
+mod mci_host_stats;
 mod mci_host_transfer;
 pub mod mci_sdif;
 pub mod sd;
 
-use core::{cell::Cell, ptr::NonNull};
+use core::cell::{Cell, RefCell};
+use core::ptr::NonNull;
+use core::time::Duration;
 
-use alloc::{boxed::Box, rc::Rc};
+use alloc::{boxed::Box, rc::Rc, sync::Arc};
 
+use crate::osa::{OsaEvent, OsaMutex};
 use constants::*;
 use err::{MCIHostError, MCIHostStatus};
 use log::error;
@@ -40,6 +45,9 @@ use mci_host_config::MCIHostConfig;
 use mci_host_device::MCIHostDevice;
 use mci_host_transfer::{MCIHostCmd, MCIHostTransfer};
 
+pub use constants::MCIHostCardStatusFlag;
+pub use mci_host_stats::{LATENCY_BUCKETS, LatencyHistogram, MCIHostStats};
+
 type MCIHostCardIntFn = fn();
 
 /// MCI Host controller.
@@ -65,6 +73,9 @@ type MCIHostCardIntFn = fn();
 /// - `tuning_type`: Tuning type for high-speed modes
 /// - `cd`: Optional card detection handler
 /// - `card_int`: Card interrupt handler
+/// - `lock`: Serializes commands on the controller
+/// - `event`: Transfer event, notified by the interrupt handler in interrupt mode
+/// - `stats`: Transfer counters
 #[allow(unused)]
 pub struct MCIHost {
     pub(crate) dev: Box<dyn MCIHostDevice>,
@@ -81,7 +92,11 @@ pub struct MCIHost {
 
     pub(crate) cd: Option<Rc<MCIHostCardDetect>>, // Card detection
     pub(crate) card_int: MCIHostCardIntFn,
-    //? Here uint8_t tuningType, sdmmc_osa_event_t hostEvent, sdmmc_osa_mutex_t lock are not ported
+    pub(crate) lock: OsaMutex<()>,
+    pub(crate) event: Arc<OsaEvent>,
+    pub(crate) stats: RefCell<MCIHostStats>,
+    pub(crate) clock_gated: Cell<bool>, // Card clock stopped while idle
+    pub(crate) last_active: Cell<Duration>, // Time of the last command
 }
 
 #[allow(unused)]
@@ -100,6 +115,11 @@ impl MCIHost {
             tuning_type: 0,
             cd: None,
             card_int: || {},
+            lock: OsaMutex::new(()),
+            event: Arc::new(OsaEvent::new()),
+            stats: RefCell::new(MCIHostStats::default()),
+            clock_gated: Cell::new(false),
+            last_active: Cell::new(Duration::ZERO),
         }
     }
 
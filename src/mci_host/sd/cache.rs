@@ -0,0 +1,517 @@
+//! # Block Cache
+//!
+//! [`CachedSdCard`] keeps recently used blocks of a [`BlockDevice`] in a
+//! fixed number of sector slots, replaced least recently used first. Writes
+//! only land in the cache and reach the device when their slot is evicted,
+//! on [`flush`](CachedSdCard::flush) or when the cache goes away. Reads
+//! that carry on where the previous one stopped fetch a few blocks ahead in
+//! the same transfer.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use log::{debug, error};
+
+use crate::mci_host::err::{
+    MCIHostError, MCIHostStatus, MCIHostTransferError, MCIHostTransferStatus,
+};
+use crate::osa::pool_buffer::PoolBuffer;
+use crate::osa::{osa_alloc_aligned, osa_init};
+
+use super::{SdCard, SharedSdCard};
+
+/// Blocks fetched past a sequential read by default
+pub const CACHE_DEFAULT_READ_AHEAD: u32 = 8;
+
+/// Storage addressed in fixed-size blocks, what [`CachedSdCard`] caches.
+pub trait BlockDevice {
+    /// Block size in bytes, a multiple of 4.
+    fn block_size(&self) -> u32;
+
+    /// Number of blocks on the device.
+    fn block_count(&self) -> u32;
+
+    /// Replace the contents of `buffer` with `block_count` blocks from `start_block`.
+    fn read_blocks(
+        &mut self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus;
+
+    /// Write `block_count` blocks from `buffer` to `start_block`.
+    fn write_blocks(
+        &mut self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus;
+}
+
+impl BlockDevice for SdCard {
+    fn block_size(&self) -> u32 {
+        SdCard::block_size(self)
+    }
+
+    fn block_count(&self) -> u32 {
+        SdCard::block_count(self)
+    }
+
+    fn read_blocks(
+        &mut self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        SdCard::read_blocks(self, buffer, start_block, block_count)
+    }
+
+    fn write_blocks(
+        &mut self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        SdCard::write_blocks(self, buffer, start_block, block_count)
+    }
+}
+
+impl BlockDevice for SharedSdCard {
+    fn block_size(&self) -> u32 {
+        SharedSdCard::block_size(self)
+    }
+
+    fn block_count(&self) -> u32 {
+        SharedSdCard::block_count(self)
+    }
+
+    fn read_blocks(
+        &mut self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        SharedSdCard::read_blocks(self, buffer, start_block, block_count)
+    }
+
+    fn write_blocks(
+        &mut self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        SharedSdCard::write_blocks(self, buffer, start_block, block_count)
+    }
+}
+
+/// Counters of a [`CachedSdCard`] since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,        // blocks read from the cache
+    pub misses: u64,      // blocks read from the device on request
+    pub read_ahead: u64,  // blocks read from the device ahead of a request
+    pub write_backs: u64, // dirty blocks written to the device
+}
+
+/* where the sector slots live */
+enum CacheStore<'a> {
+    Pool(PoolBuffer),
+    Caller(&'a mut [u32]),
+}
+
+impl CacheStore<'_> {
+    fn words(&mut self) -> &mut [u32] {
+        match self {
+            /* CachedSdCard::new checked it holds all sectors */
+            CacheStore::Pool(buffer) => buffer.as_mut_slice().unwrap_or_default(),
+            CacheStore::Caller(buffer) => buffer,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct CacheSlot {
+    lba: Option<u32>, // block held, None if the slot is free
+    dirty: bool,      // written since it was last on the device
+    used: u64,        // tick of the last access, the lowest is evicted first
+}
+
+/// Write-back LRU block cache in front of a [`BlockDevice`], an [`SdCard`]
+/// by default.
+///
+/// Dropping the cache flushes it, a failure can only be logged then. Call
+/// [`flush`](Self::flush) or [`into_inner`](Self::into_inner) to see it.
+pub struct CachedSdCard<'a, D: BlockDevice = SdCard> {
+    dev: Option<D>, // None only once into_inner_discard took it
+    store: CacheStore<'a>,
+    slots: Vec<CacheSlot>,
+    block_words: usize, // u32 words per block
+    tick: u64,
+    next_block: Option<u32>, // block after the last read, to spot sequential reads
+    read_ahead: u32,
+    stats: CacheStats,
+}
+
+impl<D: BlockDevice> CachedSdCard<'static, D> {
+    /// Cache `sectors` blocks of `dev` in a buffer from the `osa` pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::InvalidArgument`] if `sectors` is 0 or the
+    /// block size is no multiple of 4, and [`MCIHostError::NoMemory`] if the
+    /// pool can't hold the buffer.
+    pub fn new(dev: D, sectors: usize) -> MCIHostStatus<Self> {
+        let block_size = Self::block_size_check(&dev)?;
+        if sectors == 0 {
+            error!("block cache needs at least one sector!");
+            return Err(MCIHostError::InvalidArgument);
+        }
+
+        osa_init();
+        let mut buffer = osa_alloc_aligned(sectors * block_size, block_size).map_err(|e| {
+            error!(
+                "alloc {} sector(s) of block cache failed! err: {:?}",
+                sectors, e
+            );
+            MCIHostError::NoMemory
+        })?;
+        buffer.clear();
+        let words = buffer.as_mut_slice::<u32>().map_or(0, |words| words.len());
+        if words < sectors * (block_size / 4) {
+            error!(
+                "block cache buffer of {} word(s) can't hold {} sector(s)!",
+                words, sectors
+            );
+            return Err(MCIHostError::NoMemory);
+        }
+        Ok(Self::from_store(dev, CacheStore::Pool(buffer), sectors))
+    }
+}
+
+impl<'a, D: BlockDevice> CachedSdCard<'a, D> {
+    /// Cache blocks of `dev` in `buffer`, as many whole blocks as it holds.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::InvalidArgument`] if `buffer` is shorter than
+    /// a block or the block size is no multiple of 4.
+    pub fn with_buffer(dev: D, buffer: &'a mut [u32]) -> MCIHostStatus<Self> {
+        let block_size = Self::block_size_check(&dev)?;
+        let sectors = buffer.len() / (block_size / 4);
+        if sectors == 0 {
+            error!(
+                "block cache buffer of {} word(s) is shorter than a block!",
+                buffer.len()
+            );
+            return Err(MCIHostError::InvalidArgument);
+        }
+        Ok(Self::from_store(dev, CacheStore::Caller(buffer), sectors))
+    }
+
+    fn block_size_check(dev: &D) -> MCIHostStatus<usize> {
+        let block_size = dev.block_size() as usize;
+        if block_size == 0 || !block_size.is_multiple_of(4) {
+            error!("block size {} can't be cached!", block_size);
+            return Err(MCIHostError::InvalidArgument);
+        }
+        Ok(block_size)
+    }
+
+    fn from_store(dev: D, store: CacheStore<'a>, sectors: usize) -> Self {
+        let block_words = dev.block_size() as usize / 4;
+        Self {
+            dev: Some(dev),
+            store,
+            slots: vec![CacheSlot::default(); sectors],
+            block_words,
+            tick: 0,
+            next_block: None,
+            read_ahead: CACHE_DEFAULT_READ_AHEAD,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Get the device behind the cache.
+    pub fn inner(&self) -> &D {
+        self.dev.as_ref().unwrap()
+    }
+
+    fn inner_mut(&mut self) -> &mut D {
+        self.dev.as_mut().unwrap()
+    }
+
+    /// Flush the cache and take the device back.
+    ///
+    /// # Errors
+    ///
+    /// Returns the cache together with the error if the flush failed, the
+    /// blocks that didn't make it are still dirty in it.
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(mut self) -> Result<D, (Self, MCIHostTransferError)> {
+        if let Err(err) = self.flush() {
+            return Err((self, err));
+        }
+        Ok(self.into_inner_discard())
+    }
+
+    /// Take the device back, throwing away any dirty blocks in the cache.
+    pub fn into_inner_discard(mut self) -> D {
+        self.dev.take().unwrap()
+    }
+
+    /// Number of blocks the cache holds.
+    pub fn sectors(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Number of cached blocks not yet written to the device.
+    pub fn dirty_blocks(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.dirty).count()
+    }
+
+    /// Get the counters since the cache was created.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Get the number of blocks fetched past a sequential read.
+    pub fn read_ahead(&self) -> u32 {
+        self.read_ahead
+    }
+
+    /// Set the number of blocks fetched past a sequential read,
+    /// [`CACHE_DEFAULT_READ_AHEAD`] by default. 0 turns read-ahead off.
+    ///
+    /// Read-ahead never goes past the end of the device.
+    pub fn read_ahead_set(&mut self, blocks: u32) {
+        self.read_ahead = blocks;
+    }
+
+    /// Read `block_count` blocks from `start_block`, replacing the contents
+    /// of `buffer`.
+    ///
+    /// A read that starts at the block after the previous read also fetches
+    /// the next [`read_ahead`](Self::read_ahead) blocks.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::OutOfRange`] for blocks past the end of the
+    /// device, or the error of the device read or of writing back an evicted
+    /// dirty block.
+    pub fn read(
+        &mut self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        let end = self.range_check(start_block, block_count)?;
+        buffer.clear();
+        buffer.reserve(block_count as usize * self.block_words);
+        let sequential = self.next_block == Some(start_block);
+        self.next_block = Some(end);
+
+        let mut lba = start_block;
+        while lba < end {
+            if let Some(slot) = self.lookup(lba) {
+                self.stats.hits += 1;
+                self.touch(slot);
+                let range = self.slot_range(slot);
+                buffer.extend_from_slice(&self.store.words()[range]);
+                lba += 1;
+                continue;
+            }
+
+            /* fetch the whole run of missing blocks in one go */
+            let mut miss_end = lba + 1;
+            while miss_end < end && self.lookup(miss_end).is_none() {
+                miss_end += 1;
+            }
+            let mut fetch_end = miss_end;
+            if sequential && miss_end == end {
+                let ahead_end = end
+                    .saturating_add(self.read_ahead)
+                    .min(self.inner().block_count());
+                while fetch_end < ahead_end && self.lookup(fetch_end).is_none() {
+                    fetch_end += 1;
+                }
+            }
+
+            let mut fetched = Vec::new();
+            self.inner_mut()
+                .read_blocks(&mut fetched, lba, fetch_end - lba)?;
+            self.stats.misses += u64::from(miss_end - lba);
+            self.stats.read_ahead += u64::from(fetch_end - miss_end);
+            debug!(
+                "block cache miss on {} block(s) from LBA {}, {} read ahead",
+                miss_end - lba,
+                lba,
+                fetch_end - miss_end
+            );
+
+            let requested = (miss_end - lba) as usize * self.block_words;
+            buffer.extend_from_slice(&fetched[..requested]);
+            for (block, words) in (lba..fetch_end).zip(fetched.chunks(self.block_words)) {
+                let slot = self.slot_alloc()?;
+                self.slot_fill(slot, block, words, false);
+            }
+            lba = miss_end;
+        }
+
+        Ok(())
+    }
+
+    /// Write `block_count` blocks from `buffer` to `start_block` in the cache.
+    ///
+    /// The blocks reach the device when they are evicted or flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::InvalidArgument`] if `buffer` is shorter than
+    /// the blocks, [`MCIHostError::OutOfRange`] for blocks past the end of
+    /// the device, or the error of writing back an evicted dirty block.
+    pub fn write(
+        &mut self,
+        buffer: &[u32],
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        self.range_check(start_block, block_count)?;
+        if buffer.len() < block_count as usize * self.block_words {
+            error!(
+                "write buffer of {} word(s) is shorter than {} block(s)!",
+                buffer.len(),
+                block_count
+            );
+            return Err(MCIHostTransferError::from(MCIHostError::InvalidArgument)
+                .blocks_set(start_block, block_count));
+        }
+
+        let blocks = (start_block..).zip(buffer.chunks(self.block_words));
+        for (lba, words) in blocks.take(block_count as usize) {
+            let slot = match self.lookup(lba) {
+                Some(slot) => slot,
+                None => self.slot_alloc()?,
+            };
+            self.slot_fill(slot, lba, words, true);
+        }
+
+        Ok(())
+    }
+
+    /// Write every dirty block to the device, consecutive blocks in one
+    /// transfer.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first failed device write, the blocks of
+    /// that write and the ones after it stay dirty.
+    pub fn flush(&mut self) -> MCIHostTransferStatus {
+        let mut dirty: Vec<usize> = (0..self.slots.len())
+            .filter(|&slot| self.slots[slot].dirty)
+            .collect();
+        dirty.sort_unstable_by_key(|&slot| self.slots[slot].lba);
+
+        let mut start = 0;
+        while start < dirty.len() {
+            let mut end = start + 1;
+            while end < dirty.len()
+                && self.slots[dirty[end]].lba == self.slots[dirty[end - 1]].lba.map(|lba| lba + 1)
+            {
+                end += 1;
+            }
+            self.write_back(&dirty[start..end])?;
+            start = end;
+        }
+
+        Ok(())
+    }
+
+    fn range_check(&self, start_block: u32, block_count: u32) -> MCIHostTransferStatus<u32> {
+        match start_block.checked_add(block_count) {
+            Some(end) if end <= self.inner().block_count() => Ok(end),
+            _ => {
+                error!(
+                    "{} block(s) from LBA {} past the end of {} block(s)!",
+                    block_count,
+                    start_block,
+                    self.inner().block_count()
+                );
+                Err(MCIHostTransferError::from(MCIHostError::OutOfRange)
+                    .blocks_set(start_block, block_count))
+            }
+        }
+    }
+
+    fn lookup(&self, lba: u32) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.lba == Some(lba))
+    }
+
+    fn touch(&mut self, slot: usize) {
+        self.tick += 1;
+        self.slots[slot].used = self.tick;
+    }
+
+    fn slot_range(&self, slot: usize) -> core::ops::Range<usize> {
+        slot * self.block_words..(slot + 1) * self.block_words
+    }
+
+    /* a free slot, or the least recently used one once its block is on the device */
+    fn slot_alloc(&mut self) -> MCIHostTransferStatus<usize> {
+        let slot = match self.slots.iter().position(|slot| slot.lba.is_none()) {
+            Some(slot) => slot,
+            None => (0..self.slots.len())
+                .min_by_key(|&slot| self.slots[slot].used)
+                .unwrap_or_default(),
+        };
+        if self.slots[slot].dirty {
+            self.write_back(&[slot])?;
+        }
+        self.slots[slot].lba = None;
+        Ok(slot)
+    }
+
+    fn slot_fill(&mut self, slot: usize, lba: u32, words: &[u32], dirty: bool) {
+        let range = self.slot_range(slot);
+        self.store.words()[range].copy_from_slice(words);
+        self.slots[slot].lba = Some(lba);
+        self.slots[slot].dirty |= dirty;
+        self.touch(slot);
+    }
+
+    /* `run` are slots of consecutive blocks */
+    fn write_back(&mut self, run: &[usize]) -> MCIHostTransferStatus {
+        let Some(lba) = run.first().and_then(|&slot| self.slots[slot].lba) else {
+            return Ok(());
+        };
+
+        let mut buffer = Vec::with_capacity(run.len() * self.block_words);
+        for &slot in run {
+            let range = self.slot_range(slot);
+            buffer.extend_from_slice(&self.store.words()[range]);
+        }
+        self.inner_mut()
+            .write_blocks(&mut buffer, lba, run.len() as u32)?;
+
+        for &slot in run {
+            self.slots[slot].dirty = false;
+        }
+        self.stats.write_backs += run.len() as u64;
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice> Drop for CachedSdCard<'_, D> {
+    fn drop(&mut self) {
+        /* the device is gone if it was taken back, dirty blocks were discarded */
+        if self.dev.is_none() {
+            return;
+        }
+        if let Err(err) = self.flush() {
+            error!(
+                "block cache dropped, {} dirty block(s) lost: {}",
+                self.dirty_blocks(),
+                err
+            );
+        }
+    }
+}
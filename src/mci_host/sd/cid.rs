@@ -1,4 +1,4 @@
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct SdCid {
     pub manufacturer_id: u8,
     pub application_id: u16,
@@ -10,6 +10,8 @@
 //! - SD commands and application commands
 //! - Card capability flags
 
+use core::time::Duration;
+
 use bitflags::bitflags;
 
 /// SD bus timing mode enumeration.
@@ -184,4 +186,5 @@ pub(crate) const SD_PRODUCT_NAME_BYTES: usize = 5;
 pub(crate) const SD_MAX_RW_BLK: usize = 1024;
 pub(crate) const SD_BLOCK_SIZE: usize = 512;
 
-pub(crate) const SD_CARD_ACCESS_WAIT_IDLE_TIMEOUT: u32 = 600;
+/// Time the card may take to finish power up, answering ACMD41 with busy
+pub(crate) const SD_OCR_HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(1000);
@@ -0,0 +1,162 @@
+//! # Card Hot-Plug
+//!
+//! Tracks insertion and removal of the card after init. The card-detect level
+//! is read from the controller (`CDETECT`) or from a user GPIO callback and
+//! debounced; a change invokes the user callback, a reinserted card is
+//! initialized again. With the controller card-detect interrupt, the
+//! interrupt handler acknowledges it with [`MCI::card_detect_irq_ack`] and
+//! wakes the hot-plug task through [`SdCard::hotplug_notifier`].
+//!
+//! [`MCI::card_detect_irq_ack`]: crate::mci::MCI::card_detect_irq_ack
+
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::time::Duration;
+
+use log::{info, warn};
+
+use crate::mci_host::constants::MCIHostDetectCardType;
+use crate::mci_host::err::{MCIHostError, MCIHostStatus};
+use crate::mci_host::mci_host_card_detect::MCIHostCardDetect;
+use crate::mci_host::mci_sdif::constants::SDStatus;
+use crate::osa::OsaEvent;
+use crate::sleep;
+
+use super::SdCard;
+
+/// Card presence change reported by [`SdCard::hotplug_poll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdHotplugEvent {
+    /// A card was inserted and initialized
+    Inserted,
+    /// A card was inserted but failed to initialize
+    InitFailed(MCIHostError),
+    /// The card was removed
+    Removed,
+}
+
+/// Callback invoked on every [`SdHotplugEvent`].
+pub type SdHotplugFn = fn(SdHotplugEvent);
+
+impl SdCard {
+    /// Whether the card is present and initialized.
+    pub fn is_present(&self) -> bool {
+        self.present
+    }
+
+    /// Set the callback invoked when the card is inserted or removed.
+    pub fn hotplug_callback_set(&mut self, callback: SdHotplugFn) {
+        self.hotplug_callback = Some(callback);
+    }
+
+    /// Detect the card through a GPIO instead of the controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `card_detected` - Returns whether the card-detect GPIO reports a card
+    /// * `debounce_ms` - Time the level must stay stable before a change is accepted
+    pub fn card_detect_gpio_set(&mut self, card_detected: fn() -> bool, debounce_ms: u32) {
+        let mut cd = MCIHostCardDetect::new();
+        cd.typ = MCIHostDetectCardType::ByGpioCD;
+        cd.cd_debounce_ms = debounce_ms;
+        cd.card_detected = Some(card_detected);
+        let cd = Rc::new(cd);
+        if let Some(host) = self.base.host.as_mut() {
+            host.cd = Some(cd.clone());
+        }
+        self.usr_param.cd = Some(cd);
+    }
+
+    /// Event to notify from the card-detect interrupt handler, it wakes
+    /// [`hotplug_wait`](Self::hotplug_wait).
+    pub fn hotplug_notifier(&self) -> Arc<OsaEvent> {
+        self.hotplug_event.clone()
+    }
+
+    /// Wait for a notification from the interrupt handler or `timeout`, then
+    /// check the card presence.
+    ///
+    /// Without the interrupt, `timeout` is the polling period.
+    pub fn hotplug_wait(&mut self, timeout: Duration) -> Option<SdHotplugEvent> {
+        self.hotplug_event.wait(timeout);
+        self.hotplug_poll()
+    }
+
+    /// Check the card presence and handle a change.
+    ///
+    /// A removed card makes further I/O fail with [`MCIHostError::NoCard`], an
+    /// inserted card is initialized again. The callback set with
+    /// [`hotplug_callback_set`](Self::hotplug_callback_set) gets the event too.
+    ///
+    /// Returns `None` if the presence didn't change.
+    pub fn hotplug_poll(&mut self) -> Option<SdHotplugEvent> {
+        let detected = self.card_detected().ok()?;
+        if detected == self.present {
+            return None;
+        }
+
+        /* accept the new level only once it's stable */
+        sleep(Duration::from_millis(self.card_detect_debounce_ms() as u64));
+        if self.card_detected().ok()? != detected {
+            return None;
+        }
+
+        let event = if detected {
+            info!("SD card inserted");
+            match self.card_reinit() {
+                Ok(()) => SdHotplugEvent::Inserted,
+                Err(err) => {
+                    warn!("SD card re-init failed: {:?}", err);
+                    SdHotplugEvent::InitFailed(err)
+                }
+            }
+        } else {
+            info!("SD card removed");
+            self.card_removed();
+            SdHotplugEvent::Removed
+        };
+
+        if let Some(callback) = self.hotplug_callback {
+            callback(event);
+        }
+        Some(event)
+    }
+
+    /// Current card-detect level, not debounced.
+    pub(crate) fn card_detected(&self) -> MCIHostStatus<bool> {
+        let cd = self
+            .usr_param
+            .cd
+            .as_ref()
+            .ok_or(MCIHostError::HostNotReady)?;
+        if cd.typ == MCIHostDetectCardType::ByGpioCD {
+            let card_detected = cd.card_detected.ok_or(MCIHostError::Fail)?;
+            return Ok(card_detected());
+        }
+        let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
+        Ok(host.dev.card_detect_status() == SDStatus::Inserted)
+    }
+
+    /// Mark the card gone after it was pulled, further I/O fails with `NoCard`.
+    pub(crate) fn card_removed(&mut self) {
+        self.present = false;
+        self.suspended = false;
+        let _ = self.card_power_set(false);
+    }
+
+    fn card_detect_debounce_ms(&self) -> u32 {
+        self.usr_param
+            .cd
+            .as_ref()
+            .map(|cd| cd.cd_debounce_ms)
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn card_reinit(&mut self) -> MCIHostStatus {
+        self.host_do_reset()?;
+        self.card_init().map_err(|_| MCIHostError::CardInitFailed)?;
+        self.present = true;
+        self.suspended = false;
+        Ok(())
+    }
+}
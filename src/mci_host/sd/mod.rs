@@ -11,18 +11,43 @@
 //! ## Main Structure
 //!
 //! [`SdCard`] - Main SD card driver structure
+//!
+//! [`SharedSdCard`] - `Send + Sync` handle serializing access to one card
+//!
+//! [`CachedSdCard`] - Write-back block cache in front of a card
 
 #![allow(dead_code)]
+mod cache;
 mod cid;
 pub(crate) mod constants;
 mod csd;
+mod hotplug;
 mod io_voltage;
+mod power;
+#[cfg(feature = "dma")]
+mod scatter;
 mod scr;
+mod shared;
 mod status;
+#[cfg(feature = "dma")]
+mod stream;
+mod timeouts;
 mod usr_param;
+mod verify;
+
+pub use cache::{BlockDevice, CACHE_DEFAULT_READ_AHEAD, CacheStats, CachedSdCard};
+pub use hotplug::{SdHotplugEvent, SdHotplugFn};
+pub use shared::SharedSdCard;
+#[cfg(feature = "dma")]
+pub use stream::{SdReadStream, SdWriteStream};
+pub use timeouts::SdTimeouts;
+pub use verify::SdWriteVerify;
+
+pub use crate::tools::crc32;
 
 use alloc::boxed::Box;
 use alloc::rc::Rc;
+use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::cmp::max;
@@ -31,15 +56,17 @@ use core::str;
 use core::time::Duration;
 use io_voltage::SdIoVoltage;
 
-use crate::mci_host::MCIHost;
+#[cfg(feature = "cmd-trace")]
+use crate::mci::MCICmdTraceEntry;
 use crate::mci_host::mci_host_config::MCIHostType;
 use crate::mci_host::mci_sdif::sdif_device::SDIFDev;
-use crate::osa::{osa_alloc_aligned, osa_init};
-use crate::tools::swap_word_byte_sequence_u32;
-use crate::{IoPad, sleep};
+use crate::mci_host::{MCIHost, MCIHostStats};
+use crate::osa::{OsaEvent, osa_alloc_aligned, osa_init};
+use crate::tools::{Deadline, swap_word_byte_sequence_u32};
+use crate::{IoPad, now, sleep};
 
 use super::constants::*;
-use super::err::{MCIHostError, MCIHostStatus};
+use super::err::{MCIHostError, MCIHostStatus, MCIHostTransferError, MCIHostTransferStatus};
 use super::mci_card_base::MCICardBase;
 use super::mci_host_card_detect::MCIHostCardDetect;
 use super::mci_host_config::MCIHostConfig;
@@ -73,6 +100,12 @@ use usr_param::SdUsrParam;
 /// - `csd` - Card Specific Data register
 /// - `scr` - SD Configuration register
 /// - `stat` - SD status register
+/// - `present` - Card is inserted and initialized
+/// - `hotplug_callback` - User callback for insertion and removal
+/// - `hotplug_event` - Notified by the card-detect interrupt handler
+/// - `write_verify` - How writes are checked to have reached the card
+/// - `suspended` - Card is deselected with its clock stopped
+/// - `idle_clock_gate` - Idle time after which the bus clock is stopped
 pub struct SdCard {
     base: MCICardBase,
     usr_param: SdUsrParam,
@@ -87,6 +120,14 @@ pub struct SdCard {
     csd: SdCsd,
     scr: SdScr,
     stat: SdStatus,
+    timeouts: SdTimeouts,
+    timeouts_override: Option<SdTimeouts>,
+    present: bool,
+    hotplug_callback: Option<SdHotplugFn>,
+    hotplug_event: Arc<OsaEvent>,
+    write_verify: SdWriteVerify,
+    suspended: bool,
+    idle_clock_gate: Option<Duration>,
 }
 
 impl SdCard {
@@ -168,6 +209,107 @@ impl SdCard {
         self.block_count
     }
 
+    /// Get the timeouts currently in effect.
+    pub fn timeouts(&self) -> SdTimeouts {
+        self.timeouts
+    }
+
+    /// Override the timeouts derived from the card registers.
+    ///
+    /// The override is kept across re-initialization of the card.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeouts` - Timeouts to use instead of the derived ones
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCIHostError::HostNotReady` if the host has not been set up.
+    pub fn timeouts_set(&mut self, timeouts: SdTimeouts) -> MCIHostStatus {
+        self.timeouts_override = Some(timeouts);
+        self.timeouts_update()
+    }
+
+    /// Wait for command and data completion on the controller interrupt
+    /// instead of polling.
+    ///
+    /// The interrupt handler acknowledges it with [`MCI::transfer_irq_ack`]
+    /// and notifies the event from [`transfer_notifier`](Self::transfer_notifier).
+    ///
+    /// [`MCI::transfer_irq_ack`]: crate::mci::MCI::transfer_irq_ack
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCIHostError::HostNotReady` if the host has not been set up.
+    pub fn transfer_irq_set(&mut self, enable: bool) -> MCIHostStatus {
+        let host = self.base.host.as_mut().ok_or(MCIHostError::HostNotReady)?;
+        host.config.enable_irq = enable;
+        host.dev.irq_event_set(enable.then(|| host.event.clone()));
+        Ok(())
+    }
+
+    /// Event to notify from the transfer interrupt handler, `None` without a host.
+    pub fn transfer_notifier(&self) -> Option<Arc<OsaEvent>> {
+        self.base.host.as_ref().map(|host| host.event.clone())
+    }
+
+    /// Log the last commands issued to the card, e.g. after an error.
+    #[cfg(feature = "cmd-trace")]
+    pub fn cmd_trace_dump(&self) {
+        if let Some(host) = self.base.host.as_ref() {
+            host.dev.cmd_trace_dump();
+        }
+    }
+
+    /// Remove and return the last commands issued to the card, oldest first.
+    #[cfg(feature = "cmd-trace")]
+    pub fn cmd_trace_drain(&mut self) -> Vec<MCICmdTraceEntry> {
+        self.base
+            .host
+            .as_ref()
+            .map(|host| host.dev.cmd_trace_drain())
+            .unwrap_or_default()
+    }
+
+    /// Get the transfer counters and latency histograms collected so far.
+    pub fn stats(&self) -> MCIHostStats {
+        self.base
+            .host
+            .as_ref()
+            .map(|host| *host.stats.borrow())
+            .unwrap_or_default()
+    }
+
+    /// Restart counting from zero.
+    pub fn stats_reset(&mut self) {
+        self.stats_update(|stats| *stats = MCIHostStats::default());
+    }
+
+    fn stats_update(&self, f: impl FnOnce(&mut MCIHostStats)) {
+        if let Some(host) = self.base.host.as_ref() {
+            f(&mut host.stats.borrow_mut());
+        }
+    }
+
+    /// Recompute the timeouts and hand the command timeout to the controller.
+    fn timeouts_update(&mut self) -> MCIHostStatus {
+        self.timeouts = self.timeouts_override.unwrap_or_else(|| {
+            SdTimeouts::from_csd(
+                &self.csd,
+                self.flags.contains(SdCardFlag::SupportSdxc),
+                self.base.bus_clk_hz,
+            )
+        });
+        self.data_timeout_apply(self.timeouts.read)
+    }
+
+    /// Set the controller's data phase timeout for the next transfer.
+    fn data_timeout_apply(&self, data_timeout: Duration) -> MCIHostStatus {
+        let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
+        host.dev.timeouts_set(self.timeouts.cmd, data_timeout);
+        Ok(())
+    }
+
     /// Configure the host controller for SDIF operation.
     fn sdif_config(&mut self) -> MCIHostStatus {
         let mut card_cd = MCIHostCardDetect::new();
@@ -258,10 +400,24 @@ impl SdCard {
             csd: SdCsd::new(),
             scr: SdScr::new(),
             stat: SdStatus::new(),
+            timeouts: SdTimeouts::default(),
+            timeouts_override: None,
+            present: false,
+            hotplug_callback: None,
+            hotplug_event: Arc::new(OsaEvent::new()),
+            write_verify: SdWriteVerify::Off,
+            suspended: false,
+            idle_clock_gate: None,
         }
     }
 }
 
+impl Drop for SdCard {
+    fn drop(&mut self) {
+        self.teardown();
+    }
+}
+
 /// SD Card Operations
 ///
 /// This impl block provides SD card initialization and data transfer operations.
@@ -296,6 +452,7 @@ impl SdCard {
                     warn!("SD card init failed !!! {:?}", err);
                     return Err(MCIHostError::CardInitFailed);
                 }
+                self.present = true;
             }
         }
 
@@ -303,12 +460,47 @@ impl SdCard {
         status
     }
 
-    fn deinit(&self) -> MCIHostStatus {
-        let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
-        if host.dev.reset().is_err() {
-            return Err(MCIHostError::Fail);
+    /// Shut the card and the controller down and hand back the I/O pad.
+    ///
+    /// This function:
+    /// 1. Waits for the card to finish programming, aborting a transfer it's still in
+    /// 2. Deselects the card and powers it off
+    /// 3. Stops the controller, powers it off and frees the DMA descriptors
+    /// 4. Frees the internal buffer
+    ///
+    /// Dropping the card does the same, dropping the I/O pad with it.
+    pub fn deinit(mut self) -> Option<IoPad> {
+        self.teardown()
+    }
+
+    /* does nothing once the host is gone, so deinit and drop can both run it */
+    fn teardown(&mut self) -> Option<IoPad> {
+        self.base.host.as_ref()?;
+
+        if self.present && !self.suspended {
+            if Err(MCIHostError::CardStatusIdle)
+                != self.polling_card_status_busy(self.timeouts.busy)
+            {
+                warn!("card not idle, abort the transfer in progress");
+                let _ = self.transmission_stop();
+                let _ = self.polling_card_status_busy(self.timeouts.busy);
+            }
+            if self.card_select(false).is_err() {
+                warn!("deselect card failed");
+            }
         }
-        Ok(())
+        if self.present {
+            let _ = self.card_power_set(false);
+        }
+        self.present = false;
+        self.suspended = false;
+
+        let host = self.base.host.take()?;
+        host.dev.deinit();
+        let iopad = host.dev.iopad_take();
+        self.base.is_host_ready = false;
+        info!("SD card deinited");
+        iopad
     }
 
     fn card_init(&mut self) -> MCIHostStatus {
@@ -407,6 +599,8 @@ impl SdCard {
             return Err(MCIHostError::SwitchBusTimingFailed);
         }
 
+        self.timeouts_update()?;
+
         self.card_dump();
 
         Ok(())
@@ -557,11 +751,7 @@ impl SdCard {
             .cd
             .as_ref()
             .ok_or(MCIHostError::HostNotReady)?;
-        if cd.typ == MCIHostDetectCardType::ByGpioCD || cd.typ == MCIHostDetectCardType::ByHostDATA3
-        {
-            info!("SD card init start");
-            let _ = host.dev.card_detect_init(cd);
-        }
+        let _ = host.dev.card_detect_init(cd);
 
         /* set the host status flag, after the card re-plug in, don't need init host again */
         self.base.is_host_ready = true;
@@ -609,7 +799,13 @@ impl SdCard {
         if cd.typ == MCIHostDetectCardType::ByGpioCD {
             let card_detect = cd.card_detected.ok_or(MCIHostError::Fail)?;
 
+            let deadline = Deadline::after(self.timeouts.card_detect);
             loop {
+                if deadline.expired() {
+                    info!("Wait card status timeout !!!");
+                    return Err(MCIHostError::Timeout);
+                }
+
                 if card_detect() && status == SDStatus::Inserted {
                     let cd_debounce_ms = cd.cd_debounce_ms;
                     sleep(Duration::from_millis(cd_debounce_ms as u64));
@@ -633,7 +829,7 @@ impl SdCard {
             let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
             if host
                 .dev
-                .card_detect_status_polling(status, u32::MAX, host)
+                .card_detect_status_polling(status, self.timeouts.card_detect, host)
                 .is_err()
             {
                 info!("Polling SD card status failed !!!");
@@ -643,10 +839,17 @@ impl SdCard {
         Ok(())
     }
 
-    fn polling_card_status_busy(&mut self, timeout_ms: u32) -> MCIHostStatus {
-        let mut status_timeout_us = timeout_ms * 1000;
+    fn polling_card_status_busy(&mut self, timeout: Duration) -> MCIHostStatus {
+        let start = now();
+        let status = self.card_status_busy_wait(timeout);
+        self.stats_update(|stats| stats.busy_wait += now().saturating_sub(start));
+        status
+    }
+
+    fn card_status_busy_wait(&mut self, timeout: Duration) -> MCIHostStatus {
+        let deadline = Deadline::after(timeout);
 
-        while status_timeout_us > 0 {
+        while !deadline.expired() {
             let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
             if !host.dev.card_is_busy() {
                 if Err(MCIHostError::CardStatusIdle) == self.card_status_send() {
@@ -655,16 +858,13 @@ impl SdCard {
             } else {
                 /* Delay 125us to throttle the polling rate */
                 sleep(Duration::from_micros(125));
-                status_timeout_us -= 125;
             }
         }
         Err(MCIHostError::CardStatusBusy)
     }
 
     fn write_successful_block_send(&mut self, blocks: &mut u32) -> MCIHostStatus {
-        if Err(MCIHostError::CardStatusIdle)
-            != self.polling_card_status_busy(SD_CARD_ACCESS_WAIT_IDLE_TIMEOUT)
-        {
+        if Err(MCIHostError::CardStatusIdle) != self.polling_card_status_busy(self.timeouts.busy) {
             return Err(MCIHostError::WaitWriteCompleteFailed);
         }
 
@@ -696,7 +896,7 @@ impl SdCard {
                 "\r\n\r\nError: send ACMD22 failed with host error {:?}, response {:x}\r\n",
                 result, response[0]
             );
-            return result;
+            return result.map_err(MCIHostError::from);
         } else {
             *blocks = swap_word_byte_sequence_u32(response[0]);
         }
@@ -945,7 +1145,7 @@ impl SdCard {
         buffer: &mut Vec<u32>,
         start_block: u32,
         block_count: u32,
-    ) -> MCIHostStatus {
+    ) -> MCIHostTransferStatus {
         buffer.clear();
         let mut block_left = block_count;
         let mut block_count_one_time: u32;
@@ -963,16 +1163,14 @@ impl SdCard {
 
             let len = block_count_one_time * MCI_HOST_DEFAULT_BLOCK_SIZE / 4;
             let mut once_buffer = vec![0u32; len as usize];
-            if self
-                .read(
-                    &mut once_buffer,
-                    start_block,
-                    MCI_HOST_DEFAULT_BLOCK_SIZE,
-                    block_count_one_time,
-                )
-                .is_err()
-            {
-                return Err(MCIHostError::TransferFailed);
+            let lba = start_block + block_count - block_left - block_count_one_time;
+            if let Err(err) = self.read(
+                &mut once_buffer,
+                lba,
+                MCI_HOST_DEFAULT_BLOCK_SIZE,
+                block_count_one_time,
+            ) {
+                return Err(err.blocks_set_if_unset(lba, block_count_one_time));
             }
 
             buffer.extend(once_buffer.iter());
@@ -994,7 +1192,7 @@ impl SdCard {
         buffer: &mut Vec<u32>,
         start_block: u32,
         block_count: u32,
-    ) -> MCIHostStatus {
+    ) -> MCIHostTransferStatus {
         let mut block_left = block_count;
         let mut block_count_one_time: u32;
         let mut block_written_one_time = 0; // Number of blocks successfully written in one write operation
@@ -1020,18 +1218,16 @@ impl SdCard {
             #[allow(clippy::cast_possible_truncation)]
             let end = end_addr as usize;
             once_buffer.copy_from_slice(&buffer[start..end]);
-            if self
-                .write(
-                    &mut once_buffer,
-                    start_block + block_count - block_left,
-                    MCI_HOST_DEFAULT_BLOCK_SIZE,
-                    block_count_one_time,
-                    &mut block_written_one_time,
-                )
-                .is_err()
-            {
-                error!("write block(s) failed!");
-                return Err(MCIHostError::TransferFailed);
+            let lba = start_block + block_count - block_left;
+            if let Err(err) = self.write(
+                &mut once_buffer,
+                lba,
+                MCI_HOST_DEFAULT_BLOCK_SIZE,
+                block_count_one_time,
+                &mut block_written_one_time,
+            ) {
+                error!("write block(s) failed: {}", err);
+                return Err(err.blocks_set_if_unset(lba, block_count_one_time));
             }
 
             block_left -= block_count_one_time;
@@ -1040,58 +1236,185 @@ impl SdCard {
         Ok(())
     }
 
-    fn transfer(&mut self, content: &mut MCIHostTransfer, retry: u32) -> MCIHostStatus {
+    /// Erase blocks of the SD card, CMD 32/33/38.
+    ///
+    /// Erased blocks read back as all zeros or all ones, depending on the card.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_block` - First block to erase
+    /// * `block_count` - Number of blocks to erase
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the card rejects the range, or
+    /// [`MCIHostError::Timeout`] if it's still busy after the erase timeout
+    /// of every block, see [`SdTimeouts::erase`].
+    pub fn erase_blocks(&mut self, start_block: u32, block_count: u32) -> MCIHostTransferStatus {
+        if block_count == 0 {
+            return Ok(());
+        }
+        self.card_ready_check()?;
+        let end_block = start_block
+            .checked_add(block_count - 1)
+            .ok_or(MCIHostError::OutOfRange)?;
+
+        if Err(MCIHostError::CardStatusIdle) != self.polling_card_status_busy(self.timeouts.busy) {
+            error!("Error : erase failed with wrong card busy\r\n");
+            return Err(MCIHostError::PollingCardIdleFailed.into());
+        }
+
+        let high_capacity = self.flags.contains(SdCardFlag::SupportHighCapacity);
+        let erase_cmds = [
+            (
+                SdCmd::EraseWriteBlockStart as u32,
+                start_block,
+                MCIHostResponseType::R1,
+            ),
+            (
+                SdCmd::EraseWriteBlockEnd as u32,
+                end_block,
+                MCIHostResponseType::R1,
+            ),
+            (MCIHostCommonCmd::Erase as u32, 0, MCIHostResponseType::R1b),
+        ];
+        for (index, block, response_type) in erase_cmds {
+            let mut command = MCIHostCmd::new();
+            command.index_set(index);
+            command.argument_set(
+                if high_capacity || index == MCIHostCommonCmd::Erase as u32 {
+                    block
+                } else {
+                    block * MCI_HOST_DEFAULT_BLOCK_SIZE
+                },
+            );
+            command.response_type_set(response_type);
+            command.response_error_flags_set(MCIHostCardStatusFlag::ALL_ERROR_FLAG);
+
+            let mut content = MCIHostTransfer::new();
+            content.set_cmd(Some(command));
+            if let Err(err) = self.transfer(&mut content, 1) {
+                error!("erase CMD-{} failed: {}", index, err);
+                return Err(err.blocks_set_if_unset(start_block, block_count));
+            }
+        }
+
+        /* the card stays busy until all blocks are erased */
+        let timeout = self.timeouts.erase.saturating_mul(block_count);
+        if Err(MCIHostError::CardStatusIdle) != self.polling_card_status_busy(timeout) {
+            error!(
+                "erase of {} blocks didn't finish in {:?}",
+                block_count, timeout
+            );
+            return Err(MCIHostTransferError::from(MCIHostError::Timeout)
+                .blocks_set(start_block, block_count));
+        }
+        Ok(())
+    }
+
+    /// Attach the command, card status and controller status of `content` to `kind`.
+    fn transfer_error(content: &MCIHostTransfer, kind: MCIHostError) -> MCIHostTransferError {
+        let mut err = MCIHostTransferError::from(kind).raw_ints_set(content.raw_ints());
+        if let Some(cmd) = content.cmd() {
+            err = err.cmd_set(cmd.index(), cmd.argument());
+            if matches!(
+                cmd.response_type(),
+                MCIHostResponseType::R1 | MCIHostResponseType::R1b
+            ) {
+                err = err.card_status_set(MCIHostCardStatusFlag::from_bits_truncate(
+                    cmd.response()[0] & MCIHostCardStatusFlag::ALL_ERROR_FLAG.bits(),
+                ));
+            }
+        }
+        err
+    }
+
+    fn transfer(&mut self, content: &mut MCIHostTransfer, retry: u32) -> MCIHostTransferStatus {
         let mut retry = retry;
         let mut retuning_count = 3;
         loop {
             let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
-            let status = host.dev.transfer_function(content, host);
-            if status.is_ok() {
-                break;
+            let err = match host.dev.transfer_function(content, host) {
+                /* the card reports addressing and protection errors in the R1 status */
+                Ok(()) => match content.cmd() {
+                    Some(cmd) if cmd.response()[0] & cmd.response_error_flags().bits() != 0 => {
+                        error!(
+                            "CMD-{} card status error 0x{:x}",
+                            cmd.index(),
+                            cmd.response()[0]
+                        );
+                        MCIHostError::TransferFailed
+                    }
+                    _ => return Ok(()),
+                },
+                Err(err) => err,
+            };
+
+            /* a pulled card fails every command, don't retry or retune */
+            if matches!(self.card_detected(), Ok(false)) {
+                self.card_removed();
+                return Err(Self::transfer_error(content, MCIHostError::NoCard));
             }
 
             /* if transfer data failed, send cmd12 to abort current transfer */
             if content.data().is_some() {
+                self.stats_update(|stats| stats.aborts += 1);
                 let _ = self.transmission_stop();
                 /* when transfer error occur, polling card status until it is ready for next data transfer, otherwise the
                  * retry transfer will fail again */
                 if Err(MCIHostError::CardStatusIdle)
-                    != self.polling_card_status_busy(SD_CARD_ACCESS_WAIT_IDLE_TIMEOUT)
+                    != self.polling_card_status_busy(self.timeouts.busy)
                 {
-                    return Err(MCIHostError::TransferFailed);
+                    return Err(Self::transfer_error(content, err));
                 }
             }
 
-            if retry == 0 || status == Err(MCIHostError::ReTuningRequest) {
-                if self.current_timing == SdTimingMode::SDR104Mode
-                    || self.current_timing == SdTimingMode::SDR50Mode
-                {
-                    if retuning_count == 0 {
-                        break;
-                    }
-                    retuning_count -= 1;
-                    /* Perform retuning, CMD19 sends a tuning block to the host to determine sampling point.
-                    UHS50 and UHS104 cards support CMD19 in 1.8V signaling. Sampling
-                    clock tuning is required for UHS104 host and optional for UHS50 host. */
-                    if self.execute_tuning().is_err() {
-                        info!("\r\nError: retuning failed.\r\n");
-                        return Err(MCIHostError::TuningFail);
-                    } else {
-                        info!("\r\nlog: retuning successfully.\r\n");
-                        continue;
-                    }
+            /* CRC errors at UHS timings usually mean the sampling point drifted */
+            let retune_hint = matches!(
+                err,
+                MCIHostError::ReTuningRequest
+                    | MCIHostError::ResponseCrcError
+                    | MCIHostError::DataCrcError
+            );
+            if (retry == 0 || retune_hint)
+                && (self.current_timing == SdTimingMode::SDR104Mode
+                    || self.current_timing == SdTimingMode::SDR50Mode)
+            {
+                if retuning_count == 0 {
+                    return Err(Self::transfer_error(content, err));
+                }
+                retuning_count -= 1;
+                self.stats_update(|stats| stats.retunes += 1);
+                /* Perform retuning, CMD19 sends a tuning block to the host to determine sampling point.
+                UHS50 and UHS104 cards support CMD19 in 1.8V signaling. Sampling
+                clock tuning is required for UHS104 host and optional for UHS50 host. */
+                if self.execute_tuning().is_err() {
+                    info!("\r\nError: retuning failed.\r\n");
+                    return Err(Self::transfer_error(content, MCIHostError::TuningFail));
+                } else {
+                    info!("\r\nlog: retuning successfully.\r\n");
+                    continue;
                 }
-            } else {
-                break;
             }
 
-            if retry != 0 {
-                retry -= 1;
-            } else {
-                break;
+            /* host-side errors and a missing card won't go away by retrying */
+            let retryable = matches!(
+                err,
+                MCIHostError::Timeout
+                    | MCIHostError::ResponseTimeout
+                    | MCIHostError::ResponseCrcError
+                    | MCIHostError::DataCrcError
+                    | MCIHostError::DataTimeout
+                    | MCIHostError::FifoError
+                    | MCIHostError::DmaError
+                    | MCIHostError::ReTuningRequest
+            );
+            if retry == 0 || !retryable {
+                return Err(Self::transfer_error(content, err));
             }
+            retry -= 1;
+            self.stats_update(|stats| stats.retries += 1);
         }
-        Ok(())
     }
 }
 
@@ -1472,7 +1795,30 @@ impl SdCard {
         start_block: u32,
         block_size: u32,
         block_count: u32,
-    ) -> MCIHostStatus {
+    ) -> MCIHostTransferStatus {
+        let mut data = MCIHostData::new();
+        let len = block_size * block_count / 4;
+        let tmp_buf = vec![0; len as usize];
+        data.rx_data_set(Some(tmp_buf));
+
+        let mut context = self.read_data(data, start_block, block_size, block_count)?;
+
+        let data = context.data_mut().unwrap();
+        let rx_data = data.rx_data().unwrap();
+        buffer.clear();
+        buffer.extend(rx_data);
+        Ok(())
+    }
+
+    /// CMD 17/18 moving the blocks to or from the buffer set up in `data`
+    fn read_data(
+        &mut self,
+        mut data: MCIHostData,
+        start_block: u32,
+        block_size: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus<MCIHostTransfer> {
+        self.card_ready_check()?;
         if (self.flags.contains(SdCardFlag::SupportHighCapacity) && block_size != 512)
             || (block_size > self.base.block_size)
             || ({
@@ -1485,15 +1831,14 @@ impl SdCard {
                 "\r\nError: read with parameter, block size {} is not support\r\n",
                 block_size
             );
-            return Err(MCIHostError::CardNotSupport);
+            return Err(MCIHostError::CardNotSupport.into());
         }
 
+        let start = now();
         /* read command are not allowed while card is programming */
-        if Err(MCIHostError::CardStatusIdle)
-            != self.polling_card_status_busy(SD_CARD_ACCESS_WAIT_IDLE_TIMEOUT)
-        {
+        if Err(MCIHostError::CardStatusIdle) != self.polling_card_status_busy(self.timeouts.busy) {
             info!("Error: read failed with wrong card busy\r\n");
-            return Err(MCIHostError::PollingCardIdleFailed);
+            return Err(MCIHostError::PollingCardIdleFailed.into());
         }
 
         let mut command = MCIHostCmd::new();
@@ -1518,27 +1863,32 @@ impl SdCard {
         command.response_type_set(MCIHostResponseType::R1);
         command.response_error_flags_set(MCIHostCardStatusFlag::ALL_ERROR_FLAG);
 
-        let mut data = MCIHostData::new();
         data.block_size_set(block_size as usize);
         data.block_count_set(block_count);
-
-        let len = block_size * block_count / 4;
-        let tmp_buf = vec![0; len as usize];
-        data.rx_data_set(Some(tmp_buf));
-        data.enable_auto_command12_set(true);
+        self.multi_block_end_set(&mut data);
 
         let mut context = MCIHostTransfer::new();
         context.set_cmd(Some(command));
         context.set_data(Some(data));
 
+        self.data_timeout_apply(self.timeouts.read.saturating_mul(block_count))?;
         self.transfer(&mut context, 3)?;
 
-        let data = context.data_mut().unwrap();
-        let rx_data = data.rx_data().unwrap();
-        buffer.clear();
-        buffer.extend(rx_data);
+        self.stats_update(|stats| {
+            stats.blocks_read += block_count as u64;
+            stats.bytes_read += (block_count * block_size) as u64;
+            stats.read_latency.record(now().saturating_sub(start));
+        });
+        Ok(context)
+    }
 
-        Ok(())
+    /// End a multi-block transfer with CMD23 ahead of it if the card takes
+    /// it, with a CMD12 the controller sends after the data otherwise.
+    fn multi_block_end_set(&self, data: &mut MCIHostData) {
+        let multi_block = data.block_count() > 1;
+        let cmd23 = multi_block && self.flags.contains(SdCardFlag::SupportSetBlockCountCmd);
+        data.enable_auto_command23_set(cmd23);
+        data.enable_auto_command12_set(multi_block && !cmd23);
     }
 
     /// CMD 19
@@ -1573,7 +1923,25 @@ impl SdCard {
         block_size: u32,
         block_count: u32,
         written_blocks: &mut u32,
-    ) -> MCIHostStatus {
+    ) -> MCIHostTransferStatus {
+        let mut data = MCIHostData::new();
+        // TODO Reduce memory overhead
+        let tmp_buf = buffer.clone();
+        data.tx_data_set(Some(tmp_buf));
+        self.write_data(data, start_block, block_size, block_count, written_blocks)?;
+        self.written_data_verify(buffer, start_block, block_size, block_count)
+    }
+
+    /// CMD 24/25 moving the blocks from the buffer set up in `data`
+    fn write_data(
+        &mut self,
+        mut data: MCIHostData,
+        start_block: u32,
+        block_size: u32,
+        block_count: u32,
+        written_blocks: &mut u32,
+    ) -> MCIHostTransferStatus {
+        self.card_ready_check()?;
         if (self.flags.contains(SdCardFlag::SupportHighCapacity) && block_size != 512)
             || (block_size > self.base.block_size)
             || ({
@@ -1586,14 +1954,13 @@ impl SdCard {
                 "\r\nError: write with parameter, block size {} is not support\r\n",
                 block_size
             );
-            return Err(MCIHostError::CardNotSupport);
+            return Err(MCIHostError::CardNotSupport.into());
         }
 
-        if Err(MCIHostError::CardStatusIdle)
-            != self.polling_card_status_busy(SD_CARD_ACCESS_WAIT_IDLE_TIMEOUT)
-        {
+        let start = now();
+        if Err(MCIHostError::CardStatusIdle) != self.polling_card_status_busy(self.timeouts.busy) {
             error!("Error : read failed with wrong card busy\r\n");
-            return Err(MCIHostError::PollingCardIdleFailed);
+            return Err(MCIHostError::PollingCardIdleFailed.into());
         }
 
         let mut command = MCIHostCmd::new();
@@ -1611,13 +1978,9 @@ impl SdCard {
             start_block * block_size
         });
 
-        let mut data = MCIHostData::new();
-        data.enable_auto_command12_set(false);
         data.block_size_set(block_size as usize);
         data.block_count_set(block_count);
-        // TODO Reduce memory overhead
-        let tmp_buf = buffer.clone();
-        data.tx_data_set(Some(tmp_buf));
+        self.multi_block_end_set(&mut data);
 
         *written_blocks = block_count;
 
@@ -1625,17 +1988,22 @@ impl SdCard {
         content.set_cmd(Some(command));
         content.set_data(Some(data));
 
-        if let Err(e) = self.transfer(&mut content, 3) {
-            return Err(e);
-        } else {
-            if let Err(e) = self.write_successful_block_send(written_blocks) {
-                return Err(e);
-            } else if *written_blocks == 0 {
-                return Err(MCIHostError::TransferFailed);
-            }
-            debug!("written blocks this time is {}", written_blocks);
-        }
-
+        self.data_timeout_apply(self.timeouts.write.saturating_mul(block_count))?;
+        self.transfer(&mut content, 3)?;
+        if let Err(e) = self.write_successful_block_send(written_blocks) {
+            return Err(e.into());
+        } else if *written_blocks == 0 {
+            return Err(Self::transfer_error(&content, MCIHostError::TransferFailed));
+        }
+        self.written_blocks_verify(start_block, block_count, *written_blocks)?;
+        debug!("written blocks this time is {}", written_blocks);
+
+        let written = *written_blocks;
+        self.stats_update(|stats| {
+            stats.blocks_written += written as u64;
+            stats.bytes_written += (written * block_size) as u64;
+            stats.write_latency.record(now().saturating_sub(start));
+        });
         Ok(())
     }
 
@@ -1715,8 +2083,8 @@ impl SdCard {
         let mut content = MCIHostTransfer::new();
         content.set_cmd(Some(command));
 
-        let mut i = MCI_HOST_MAX_CMD_RETRIES;
-        while i > 0 {
+        let deadline = Deadline::after(SD_OCR_HANDSHAKE_TIMEOUT);
+        while !deadline.expired() {
             if self.application_cmd_send(0).is_err() {
                 continue;
             }
@@ -1757,12 +2125,11 @@ impl SdCard {
                 return Ok(());
             }
 
-            i -= 1;
             sleep(Duration::from_millis(10));
         }
 
         info!("\r\nError: send ACMD41 timeout\r\n");
-        Ok(())
+        Err(MCIHostError::Timeout)
     }
 
     /// ACMD 51
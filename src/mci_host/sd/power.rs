@@ -0,0 +1,172 @@
+//! # Power Management
+//!
+//! Suspend deselects the card (CMD7) and stops the bus clock, cutting the
+//! card supply too if the board can switch it. Resume selects the card again
+//! or, after a power cut, initializes it from scratch and checks the CID to
+//! make sure it's still the same card. Between transfers the bus clock can
+//! also be stopped once it has been idle for a while.
+
+use core::mem::take;
+use core::time::Duration;
+
+use log::{error, info, warn};
+
+use crate::mci_host::err::{MCIHostError, MCIHostStatus};
+use crate::now;
+
+use super::SdCard;
+
+impl SdCard {
+    /// Whether the card is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Switch the card supply with `power`, e.g. through the GPIO of a
+    /// load switch.
+    ///
+    /// Without it the card stays powered while suspended.
+    pub fn power_switch_set(&mut self, power: fn(bool)) {
+        self.usr_param.sd_pwr = Some(power);
+    }
+
+    /// Suspend the card: wait for it to finish programming, deselect it and
+    /// stop the bus clock. The card is powered off if a switch was set with
+    /// [`power_switch_set`](Self::power_switch_set).
+    ///
+    /// I/O fails with [`MCIHostError::Suspended`] until [`resume`](Self::resume).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::NoCard`] without a card, or an error if the
+    /// card stays busy or can't be deselected.
+    pub fn suspend(&mut self) -> MCIHostStatus {
+        if self.suspended {
+            return Ok(());
+        }
+        if !self.present {
+            return Err(MCIHostError::NoCard);
+        }
+
+        if Err(MCIHostError::CardStatusIdle) != self.polling_card_status_busy(self.timeouts.busy) {
+            error!("card still busy, can't suspend");
+            return Err(MCIHostError::CardStatusBusy);
+        }
+        if let Err(err) = self.card_select(false) {
+            error!("deselect card failed: {:?}", err);
+            return Err(MCIHostError::DeselectCardFailed);
+        }
+
+        let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
+        host.dev.card_clock_set(0, host);
+        if self.usr_param.sd_pwr.is_some() {
+            self.card_power_set(false)?;
+        }
+
+        self.suspended = true;
+        info!("SD card suspended");
+        Ok(())
+    }
+
+    /// Resume a suspended card.
+    ///
+    /// A card that kept its power is clocked and selected again as it was.
+    /// A card that was powered off, or doesn't answer, is initialized again.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::NoCard`] if the card was pulled,
+    /// [`MCIHostError::CardInitFailed`] if it fails to initialize and
+    /// [`MCIHostError::CardChanged`] if a different card was inserted; the
+    /// new card is ready for I/O then.
+    pub fn resume(&mut self) -> MCIHostStatus {
+        if !self.suspended {
+            return Ok(());
+        }
+        if !self.card_detected()? {
+            self.card_removed();
+            return Err(MCIHostError::NoCard);
+        }
+
+        if self.usr_param.sd_pwr.is_none() {
+            let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
+            let bus_clk_hz = self.base.bus_clk_hz;
+            if host.dev.card_clock_set(bus_clk_hz, host) == bus_clk_hz
+                && self.card_select(true).is_ok()
+            {
+                self.suspended = false;
+                info!("SD card resumed");
+                return Ok(());
+            }
+            warn!("card lost its state while suspended, initialize it again");
+        }
+
+        let cid = take(&mut self.cid);
+        if let Err(err) = self.card_reinit() {
+            /* hot-plug polling retries it as a newly inserted card */
+            self.present = false;
+            self.suspended = false;
+            return Err(err);
+        }
+        if self.cid != cid {
+            warn!("another card was inserted while suspended: {:?}", self.cid);
+            return Err(MCIHostError::CardChanged);
+        }
+
+        info!("SD card resumed");
+        Ok(())
+    }
+
+    /// Get the idle time after which the bus clock is stopped, if at all.
+    pub fn idle_clock_gate(&self) -> Option<Duration> {
+        self.idle_clock_gate
+    }
+
+    /// Stop the bus clock once no command went out for `idle`, `None` (the
+    /// default) keeps it running.
+    ///
+    /// The clock is only stopped by [`idle_poll`](Self::idle_poll), the next
+    /// command restarts it.
+    pub fn idle_clock_gate_set(&mut self, idle: Option<Duration>) {
+        self.idle_clock_gate = idle;
+    }
+
+    /// Stop the bus clock if it was idle for longer than set with
+    /// [`idle_clock_gate_set`](Self::idle_clock_gate_set).
+    ///
+    /// Call it periodically, e.g. next to [`hotplug_wait`](Self::hotplug_wait).
+    /// Returns whether the clock is stopped.
+    pub fn idle_poll(&mut self) -> bool {
+        let Some(idle) = self.idle_clock_gate else {
+            return false;
+        };
+        let Some(host) = self.base.host.as_ref() else {
+            return false;
+        };
+        if !self.present || self.suspended || host.clock_gated.get() {
+            return host.clock_gated.get();
+        }
+
+        let _guard = host.lock.lock();
+        /* a card still programming is left alone */
+        if now().saturating_sub(host.last_active.get()) < idle || host.dev.card_is_busy() {
+            return false;
+        }
+        if let Err(err) = host.dev.clock_gate_set(true, host) {
+            warn!("gate idle clock failed: {:?}", err);
+            return false;
+        }
+        true
+    }
+
+    /// Fail with `NoCard` or `Suspended` unless the card takes I/O.
+    pub(crate) fn card_ready_check(&self) -> MCIHostStatus {
+        if !self.present {
+            return Err(MCIHostError::NoCard);
+        }
+        if self.suspended {
+            return Err(MCIHostError::Suspended);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+//! # Scatter-Gather Transfers
+//!
+//! Block reads and writes straight from or to a list of caller buffers given
+//! by bus address, e.g. the non-contiguous pages of a filesystem cache. A
+//! single CMD18/CMD25 covers as many segments as the descriptors allow, no
+//! bounce buffer is involved. Only built with the `dma` feature.
+
+use log::error;
+
+use crate::mci::MCIDmaSegment;
+use crate::mci::mci_dma::dma_segments_slice;
+use crate::mci_host::constants::MCI_HOST_DEFAULT_BLOCK_SIZE;
+use crate::mci_host::err::{MCIHostError, MCIHostTransferStatus};
+use crate::mci_host::mci_host_transfer::MCIHostData;
+
+use super::SdCard;
+
+impl SdCard {
+    /// Read blocks into the buffers described by `segments`.
+    ///
+    /// The driver only knows the bus addresses of the buffers, the IDMAC
+    /// writes wherever they point.
+    ///
+    /// # Arguments
+    ///
+    /// * `segments` - Buffers to fill in order, their lengths add up to whole blocks
+    /// * `start_block` - Starting block number
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::InvalidArgument`] if a segment is not aligned
+    /// to 4 bytes or the segments don't add up to whole blocks, and the
+    /// transfer error otherwise.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that every segment:
+    /// - Is memory the caller owns, mapped for the device, for the whole call
+    /// - Is not accessed by the CPU until the call returns
+    /// - Has no dirty cache line that could be evicted over the data read,
+    ///   and is invalidated before the CPU reads the data
+    pub unsafe fn read_blocks_sg(
+        &mut self,
+        segments: &[MCIDmaSegment],
+        start_block: u32,
+    ) -> MCIHostTransferStatus {
+        self.blocks_sg(segments, start_block, true)
+    }
+
+    /// Write blocks from the buffers described by `segments`.
+    ///
+    /// The driver only knows the bus addresses of the buffers, the IDMAC
+    /// reads wherever they point.
+    ///
+    /// # Arguments
+    ///
+    /// * `segments` - Buffers to send in order, their lengths add up to whole blocks
+    /// * `start_block` - Starting block number
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::InvalidArgument`] if a segment is not aligned
+    /// to 4 bytes or the segments don't add up to whole blocks, and the
+    /// transfer error otherwise.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that every segment:
+    /// - Is memory the caller may read, mapped for the device, for the whole call
+    /// - Is not written by the CPU until the call returns
+    /// - Has been cleaned from the cache so the device sees the data
+    pub unsafe fn write_blocks_sg(
+        &mut self,
+        segments: &[MCIDmaSegment],
+        start_block: u32,
+    ) -> MCIHostTransferStatus {
+        self.blocks_sg(segments, start_block, false)
+    }
+
+    fn blocks_sg(
+        &mut self,
+        segments: &[MCIDmaSegment],
+        start_block: u32,
+        read: bool,
+    ) -> MCIHostTransferStatus {
+        let block_size = MCI_HOST_DEFAULT_BLOCK_SIZE;
+        if let Some(seg) = segments
+            .iter()
+            .find(|seg| !seg.bus_addr.is_multiple_of(4) || !seg.len.is_multiple_of(4))
+        {
+            error!(
+                "scatter segment 0x{:x}+{} is not aligned to 4",
+                seg.bus_addr, seg.len
+            );
+            return Err(MCIHostError::InvalidArgument.into());
+        }
+        let total: usize = segments.iter().map(|seg| seg.len as usize).sum();
+        if total == 0 || !total.is_multiple_of(block_size as usize) {
+            error!("scatter list of {} bytes is not whole blocks", total);
+            return Err(MCIHostError::InvalidArgument.into());
+        }
+
+        let block_count = (total / block_size as usize) as u32;
+        let max_block_count = self
+            .base
+            .host
+            .as_ref()
+            .ok_or(MCIHostError::HostNotReady)?
+            .max_block_count
+            .get();
+
+        let mut done = 0;
+        while done < block_count {
+            let count = (block_count - done).min(max_block_count);
+            let lba = start_block + done;
+            let chunk = dma_segments_slice(
+                segments,
+                (done * block_size) as usize,
+                (count * block_size) as usize,
+            );
+
+            let mut data = MCIHostData::new();
+            let result = if read {
+                data.rx_segments_set(Some(chunk));
+                self.read_data(data, lba, block_size, count).map(|_| ())
+            } else {
+                let mut written_blocks = 0;
+                data.tx_segments_set(Some(chunk));
+                self.write_data(data, lba, block_size, count, &mut written_blocks)
+            };
+            if let Err(err) = result {
+                return Err(err.blocks_set_if_unset(lba, count));
+            }
+
+            done += count;
+        }
+
+        Ok(())
+    }
+}
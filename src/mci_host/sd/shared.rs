@@ -0,0 +1,121 @@
+//! # Shared SD Card Handle
+//!
+//! [`SdCard`] keeps its controller state in `Cell`/`RefCell`/`Rc`, so it
+//! can't cross tasks on its own. [`SharedSdCard`] owns the card and hands it
+//! out to one request at a time, in the order the requests arrived.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::mci_host::err::MCIHostTransferStatus;
+use crate::yield_now;
+
+use super::SdCard;
+
+struct Shared {
+    /* ticket handed to the next request */
+    next: AtomicU32,
+    /* ticket allowed to access the card */
+    serving: AtomicU32,
+    card: UnsafeCell<SdCard>,
+}
+
+// SAFETY: the `Rc`s and cells of the card are created by the card and never
+// handed out, and every access to the card goes through the ticket queue, so
+// only one task touches that state at a time.
+unsafe impl Send for Shared {}
+unsafe impl Sync for Shared {}
+
+/// `Send + Sync` handle to an [`SdCard`], cloneable across tasks.
+///
+/// Requests are queued in arrival order (a ticket lock), so readers and
+/// writers are served first-come first-served and none of them starves.
+/// While waiting for its turn a task calls [`Kernel::yield_now`](crate::Kernel::yield_now).
+#[derive(Clone)]
+pub struct SharedSdCard {
+    inner: Arc<Shared>,
+}
+
+/* advances the queue when the request finishes */
+struct Turn<'a>(&'a Shared);
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.0.serving.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl SharedSdCard {
+    /// Wrap an initialized card.
+    pub fn new(card: SdCard) -> Self {
+        Self {
+            inner: Arc::new(Shared {
+                next: AtomicU32::new(0),
+                serving: AtomicU32::new(0),
+                card: UnsafeCell::new(card),
+            }),
+        }
+    }
+
+    /// Run `f` with exclusive access to the card once all earlier requests finished.
+    ///
+    /// `f` must not call back into the same handle, that request would wait
+    /// for itself.
+    pub fn with<R>(&self, f: impl FnOnce(&mut SdCard) -> R) -> R {
+        let shared = &*self.inner;
+        let ticket = shared.next.fetch_add(1, Ordering::Relaxed);
+        while shared.serving.load(Ordering::Acquire) != ticket {
+            yield_now();
+        }
+        let _turn = Turn(shared);
+        // SAFETY: only the holder of the serving ticket gets here.
+        f(unsafe { &mut *shared.card.get() })
+    }
+
+    /// Number of requests queued or in progress.
+    pub fn pending(&self) -> u32 {
+        let shared = &*self.inner;
+        let serving = shared.serving.load(Ordering::Relaxed);
+        shared.next.load(Ordering::Relaxed).wrapping_sub(serving)
+    }
+
+    /// Read blocks, see [`SdCard::read_blocks`].
+    pub fn read_blocks(
+        &self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        self.with(|card| card.read_blocks(buffer, start_block, block_count))
+    }
+
+    /// Write blocks, see [`SdCard::write_blocks`].
+    pub fn write_blocks(
+        &self,
+        buffer: &mut Vec<u32>,
+        start_block: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        self.with(|card| card.write_blocks(buffer, start_block, block_count))
+    }
+
+    /// Block size of the card in bytes.
+    pub fn block_size(&self) -> u32 {
+        self.with(|card| card.block_size())
+    }
+
+    /// Number of blocks on the card.
+    pub fn block_count(&self) -> u32 {
+        self.with(|card| card.block_count())
+    }
+
+    /// Take the card back, fails while other handles exist.
+    pub fn into_inner(self) -> Result<SdCard, Self> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(shared) => Ok(shared.card.into_inner()),
+            Err(inner) => Err(Self { inner }),
+        }
+    }
+}
@@ -0,0 +1,489 @@
+//! # Streaming Transfers
+//!
+//! Reads and writes that keep one open-ended CMD18/CMD25 running over a ring
+//! of DMA buffers, for workloads like video capture and playback. The
+//! controller fills or drains the ring chunk by chunk while the caller
+//! works on the chunk before; a chunk goes back to the DMA as soon as the
+//! caller is done with it. CMD12 ends the stream. Only built with the `dma`
+//! feature.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use dma_api::{DVec, Direction};
+use log::{error, warn};
+
+use crate::mci_host::constants::*;
+use crate::mci_host::err::{
+    MCIHostError, MCIHostStatus, MCIHostTransferError, MCIHostTransferStatus,
+};
+use crate::mci_host::mci_host_transfer::{MCIHostCmd, MCIHostTransfer};
+use crate::regs::Backoff;
+
+use super::SdCard;
+use super::constants::{SD_BLOCK_SIZE, SdCardFlag};
+
+/// A running open-ended read, from [`SdCard::read_stream`].
+///
+/// Dropping it stops the stream like [`SdReadStream::stop`], ignoring errors.
+pub struct SdReadStream<'a> {
+    card: &'a mut SdCard,
+    bufs: Vec<DVec<u32>>,  // Ring of chunk buffers the DMA fills
+    chunk_blocks: u32,     // Blocks per chunk
+    next: usize,           // Chunk handed out next
+    block: u32,            // First block of chunk `next`
+    armed_end: u32,        // Block after the last chunk given to the DMA
+    handed: Option<usize>, // Chunk the caller holds, given back on the next call
+    stopped: bool,
+}
+
+/// A running open-ended write, from [`SdCard::write_stream`].
+///
+/// Dropping it before [`SdWriteStream::finish`] aborts the stream; the
+/// blocks of unfinished chunks may or may not be written.
+pub struct SdWriteStream<'a> {
+    card: &'a mut SdCard,
+    bufs: Vec<DVec<u32>>, // Ring of chunk buffers the DMA drains
+    chunk_blocks: u32,    // Blocks per chunk
+    next: usize,          // Chunk filled next
+    block: u32,           // First block of chunk `next`
+    in_flight: usize,     // Chunks given to the DMA and not done yet
+    started: bool,
+    stopped: bool,
+}
+
+impl SdCard {
+    /// Start reading from `start_block` in chunks of `chunk_blocks` blocks,
+    /// with up to `depth` chunks read ahead.
+    ///
+    /// The command keeps running until the stream stops or reaches the end of
+    /// the card, the card is not available for anything else meanwhile.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::InvalidArgument`] if `chunk_blocks` or `depth`
+    /// is zero, [`MCIHostError::OutOfRange`] if not a whole chunk is left
+    /// before the end of the card, [`MCIHostError::HostNotSupport`] if the
+    /// host is not in DMA mode, and the command error otherwise.
+    pub fn read_stream(
+        &mut self,
+        start_block: u32,
+        chunk_blocks: u32,
+        depth: usize,
+    ) -> MCIHostTransferStatus<SdReadStream<'_>> {
+        let bufs = self.stream_bufs(chunk_blocks, depth, Direction::FromDevice)?;
+
+        /* only whole chunks are read, and none past the end of the card */
+        let chunks_left = self.block_count.saturating_sub(start_block) / chunk_blocks;
+        let armed = depth.min(chunks_left as usize);
+        if armed == 0 {
+            return Err(MCIHostError::OutOfRange.into());
+        }
+
+        let chunks: Vec<usize> = bufs.iter().map(|buf| buf.bus_addr() as usize).collect();
+        self.stream_open(
+            false,
+            start_block,
+            &chunks,
+            chunk_blocks * MCI_HOST_DEFAULT_BLOCK_SIZE,
+            armed,
+        )?;
+
+        Ok(SdReadStream {
+            card: self,
+            bufs,
+            chunk_blocks,
+            next: 0,
+            block: start_block,
+            armed_end: start_block + armed as u32 * chunk_blocks,
+            handed: None,
+            stopped: false,
+        })
+    }
+
+    /// Start writing from `start_block` in chunks of `chunk_blocks` blocks,
+    /// with up to `depth` chunks queued for the card.
+    ///
+    /// The command goes out with the first chunk and keeps running until
+    /// [`SdWriteStream::finish`], the card is not available for anything
+    /// else meanwhile.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::InvalidArgument`] if `chunk_blocks` or `depth`
+    /// is zero and [`MCIHostError::HostNotSupport`] if the host is not in DMA mode.
+    pub fn write_stream(
+        &mut self,
+        start_block: u32,
+        chunk_blocks: u32,
+        depth: usize,
+    ) -> MCIHostTransferStatus<SdWriteStream<'_>> {
+        let bufs = self.stream_bufs(chunk_blocks, depth, Direction::ToDevice)?;
+
+        Ok(SdWriteStream {
+            card: self,
+            bufs,
+            chunk_blocks,
+            next: 0,
+            block: start_block,
+            in_flight: 0,
+            started: false,
+            stopped: false,
+        })
+    }
+
+    /// The ring of `depth` buffers of `chunk_blocks` blocks for a stream.
+    fn stream_bufs(
+        &self,
+        chunk_blocks: u32,
+        depth: usize,
+        direction: Direction,
+    ) -> MCIHostTransferStatus<Vec<DVec<u32>>> {
+        self.card_ready_check()?;
+        if chunk_blocks == 0 || depth == 0 {
+            error!(
+                "stream of {} chunk(s) of {} blocks is empty",
+                depth, chunk_blocks
+            );
+            return Err(MCIHostError::InvalidArgument.into());
+        }
+        let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
+        if !host.config.enable_dma {
+            error!("streaming needs the host in DMA mode");
+            return Err(MCIHostError::HostNotSupport.into());
+        }
+
+        let words = (chunk_blocks * MCI_HOST_DEFAULT_BLOCK_SIZE / 4) as usize;
+        (0..depth)
+            .map(|_| {
+                DVec::zeros(words, SD_BLOCK_SIZE, direction).ok_or_else(|| {
+                    error!("alloc {} words stream buffer failed!", words);
+                    MCIHostError::Fail.into()
+                })
+            })
+            .collect()
+    }
+
+    /// CMD 18/25 without a block count, moving data over the ring `chunks`.
+    fn stream_open(
+        &mut self,
+        write: bool,
+        start_block: u32,
+        chunks: &[usize],
+        chunk_len: u32,
+        armed: usize,
+    ) -> MCIHostTransferStatus {
+        if Err(MCIHostError::CardStatusIdle) != self.polling_card_status_busy(self.timeouts.busy) {
+            error!("Error: stream failed with wrong card busy");
+            return Err(MCIHostError::PollingCardIdleFailed.into());
+        }
+
+        let mut command = MCIHostCmd::new();
+        command.index_set(if write {
+            MCIHostCommonCmd::WriteMultipleBlock as u32
+        } else {
+            MCIHostCommonCmd::ReadMultipleBlock as u32
+        });
+        command.argument_set(if self.flags.contains(SdCardFlag::SupportHighCapacity) {
+            start_block
+        } else {
+            start_block * MCI_HOST_DEFAULT_BLOCK_SIZE
+        });
+        command.response_type_set(MCIHostResponseType::R1);
+        command.response_error_flags_set(MCIHostCardStatusFlag::ALL_ERROR_FLAG);
+
+        let mut content = MCIHostTransfer::new();
+        content.set_cmd(Some(command));
+
+        let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
+        let err = match host
+            .dev
+            .stream_start(&mut content, write, chunks, chunk_len, armed, host)
+        {
+            Ok(()) => match content.cmd() {
+                Some(cmd) if cmd.response()[0] & cmd.response_error_flags().bits() != 0 => {
+                    error!(
+                        "CMD-{} card status error 0x{:x}",
+                        cmd.index(),
+                        cmd.response()[0]
+                    );
+                    MCIHostError::TransferFailed
+                }
+                _ => return Ok(()),
+            },
+            Err(err) => err,
+        };
+
+        let err = Self::transfer_error(&content, err);
+        Err(self.stream_abort(write, err))
+    }
+
+    /// CMD 12 ending the running stream, then wait for the card to be idle.
+    fn stream_close(&mut self, write: bool) -> MCIHostTransferStatus {
+        let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
+
+        let mut command = MCIHostCmd::new();
+        command.index_set(MCIHostCommonCmd::StopTransmission as u32);
+        command.argument_set(0);
+        command.cmd_type_set(MCIHostCmdType::Abort);
+        command.response_type_set(MCIHostResponseType::R1b);
+
+        let mut content = MCIHostTransfer::new();
+        content.set_cmd(Some(command));
+
+        if let Err(err) = host.dev.stream_stop(&mut content, write, host) {
+            error!("Error: stop stream failed with host error {:?}", err);
+            return Err(Self::transfer_error(&content, err));
+        }
+
+        /* a write stream leaves the card programming the last blocks */
+        if Err(MCIHostError::CardStatusIdle) != self.polling_card_status_busy(self.timeouts.busy) {
+            return Err(MCIHostError::WaitWriteCompleteFailed.into());
+        }
+        Ok(())
+    }
+
+    /// Stop the running stream after `err` and return the error to report.
+    fn stream_abort(&mut self, write: bool, err: MCIHostTransferError) -> MCIHostTransferError {
+        /* a pulled card fails every command, don't try to stop it */
+        if matches!(self.card_detected(), Ok(false)) {
+            self.card_removed();
+            return MCIHostError::NoCard.into();
+        }
+        self.stats_update(|stats| stats.aborts += 1);
+        if let Err(stop_err) = self.stream_close(write) {
+            warn!("stop stream after error failed: {}", stop_err);
+        }
+        err
+    }
+
+    /// Wait up to `timeout` for the DMA to be done with `chunk`.
+    fn stream_chunk_wait(&self, chunk: usize, timeout: Duration) -> MCIHostStatus {
+        let host = self.base.host.as_ref().ok_or(MCIHostError::HostNotReady)?;
+        let mut backoff = Backoff::new(timeout);
+        while !host.dev.stream_chunk_done(chunk)? {
+            if !backoff.snooze() {
+                error!("wait stream chunk {} timeout", chunk);
+                return Err(MCIHostError::Timeout);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SdReadStream<'_> {
+    /// Wait for the next chunk and borrow it.
+    ///
+    /// The chunk before goes back to the DMA, so the returned slice must be
+    /// done with by the next call.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::OutOfRange`] at the end of the card and
+    /// [`MCIHostError::NoTransferInProgress`] once the stream stopped. A
+    /// transfer error stops the stream and is returned with the blocks of
+    /// the chunk.
+    pub fn next_chunk(&mut self) -> MCIHostTransferStatus<&[u32]> {
+        if self.stopped {
+            return Err(MCIHostError::NoTransferInProgress.into());
+        }
+
+        if let Some(chunk) = self.handed.take()
+            && self.armed_end.saturating_add(self.chunk_blocks) <= self.card.block_count
+        {
+            let host = self
+                .card
+                .base
+                .host
+                .as_ref()
+                .ok_or(MCIHostError::HostNotReady)?;
+            if let Err(err) = host.dev.stream_chunk_arm(chunk) {
+                return Err(self.fail(err));
+            }
+            self.armed_end += self.chunk_blocks;
+        }
+
+        if self.block >= self.armed_end {
+            return Err(MCIHostError::OutOfRange.into());
+        }
+
+        let timeout = self.card.timeouts.read.saturating_mul(self.chunk_blocks);
+        if let Err(err) = self.card.stream_chunk_wait(self.next, timeout) {
+            return Err(self.fail(err));
+        }
+
+        let blocks = self.chunk_blocks;
+        self.card.stats_update(|stats| {
+            stats.blocks_read += blocks as u64;
+            stats.bytes_read += (blocks * MCI_HOST_DEFAULT_BLOCK_SIZE) as u64;
+        });
+
+        let chunk = self.next;
+        self.handed = Some(chunk);
+        self.next = (chunk + 1) % self.bufs.len();
+        self.block += self.chunk_blocks;
+        /* the deref invalidates the chunk, so the CPU sees what the DMA wrote */
+        Ok(&*self.bufs[chunk])
+    }
+
+    /// First block of the chunk [`next_chunk`](Self::next_chunk) returns next.
+    pub fn position(&self) -> u32 {
+        self.block
+    }
+
+    /// Stop the stream with CMD12.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the CMD12 or of waiting for the card after it.
+    pub fn stop(mut self) -> MCIHostTransferStatus {
+        self.stopped = true;
+        self.card.stream_close(false)
+    }
+
+    fn fail(&mut self, err: MCIHostError) -> MCIHostTransferError {
+        self.stopped = true;
+        let err = MCIHostTransferError::from(err).blocks_set(self.block, self.chunk_blocks);
+        self.card.stream_abort(false, err)
+    }
+}
+
+impl Drop for SdReadStream<'_> {
+    fn drop(&mut self) {
+        if !self.stopped
+            && let Err(err) = self.card.stream_close(false)
+        {
+            warn!("stop dropped read stream failed: {}", err);
+        }
+    }
+}
+
+impl SdWriteStream<'_> {
+    /// Queue one chunk of data for the card, waiting for a free buffer if
+    /// `depth` chunks are already queued.
+    ///
+    /// `data` is copied, it must be exactly one chunk long.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::InvalidArgument`] if `data` is not one chunk,
+    /// [`MCIHostError::OutOfRange`] if the chunk ends past the card and
+    /// [`MCIHostError::NoTransferInProgress`] once the stream stopped. A
+    /// transfer error stops the stream and is returned with the blocks of
+    /// the chunk that failed.
+    pub fn write_chunk(&mut self, data: &[u32]) -> MCIHostTransferStatus {
+        if self.stopped {
+            return Err(MCIHostError::NoTransferInProgress.into());
+        }
+        if data.len() != self.bufs[0].len() {
+            error!(
+                "stream chunk of {} words, {} expected",
+                data.len(),
+                self.bufs[0].len()
+            );
+            return Err(MCIHostError::InvalidArgument.into());
+        }
+        if self.block.saturating_add(self.chunk_blocks) > self.card.block_count {
+            return Err(MCIHostError::OutOfRange.into());
+        }
+
+        if self.in_flight == self.bufs.len() {
+            self.oldest_wait()?;
+        }
+
+        let chunk = self.next;
+        self.bufs[chunk].copy_from_slice(data);
+        if self.started {
+            let host = self
+                .card
+                .base
+                .host
+                .as_ref()
+                .ok_or(MCIHostError::HostNotReady)?;
+            if let Err(err) = host.dev.stream_chunk_arm(chunk) {
+                return Err(self.fail(err, self.block));
+            }
+        } else {
+            let chunks: Vec<usize> = self
+                .bufs
+                .iter()
+                .map(|buf| buf.bus_addr() as usize)
+                .collect();
+            let chunk_len = self.chunk_blocks * MCI_HOST_DEFAULT_BLOCK_SIZE;
+            if let Err(err) = self
+                .card
+                .stream_open(true, self.block, &chunks, chunk_len, 1)
+            {
+                self.stopped = true;
+                return Err(err.blocks_set(self.block, self.chunk_blocks));
+            }
+            self.started = true;
+        }
+
+        self.in_flight += 1;
+        self.next = (chunk + 1) % self.bufs.len();
+        self.block += self.chunk_blocks;
+        Ok(())
+    }
+
+    /// First block of the chunk [`write_chunk`](Self::write_chunk) queues next.
+    pub fn position(&self) -> u32 {
+        self.block
+    }
+
+    /// Wait for the queued chunks, then stop the stream with CMD12 and wait
+    /// for the card to finish programming.
+    ///
+    /// # Errors
+    ///
+    /// Returns the transfer error of a queued chunk, or the error of the
+    /// CMD12 or of waiting for the card after it.
+    pub fn finish(mut self) -> MCIHostTransferStatus {
+        if !self.started {
+            self.stopped = true;
+            return Ok(());
+        }
+        while self.in_flight != 0 {
+            self.oldest_wait()?;
+        }
+        self.stopped = true;
+        self.card.stream_close(true)
+    }
+
+    /// Wait for the DMA to be done with the oldest queued chunk.
+    fn oldest_wait(&mut self) -> MCIHostTransferStatus {
+        let depth = self.bufs.len();
+        let chunk = (self.next + depth - self.in_flight) % depth;
+        let lba = self.block - self.in_flight as u32 * self.chunk_blocks;
+
+        let timeout = self.card.timeouts.write.saturating_mul(self.chunk_blocks);
+        if let Err(err) = self.card.stream_chunk_wait(chunk, timeout) {
+            return Err(self.fail(err, lba));
+        }
+
+        let blocks = self.chunk_blocks;
+        self.card.stats_update(|stats| {
+            stats.blocks_written += blocks as u64;
+            stats.bytes_written += (blocks * MCI_HOST_DEFAULT_BLOCK_SIZE) as u64;
+        });
+        self.in_flight -= 1;
+        Ok(())
+    }
+
+    fn fail(&mut self, err: MCIHostError, lba: u32) -> MCIHostTransferError {
+        self.stopped = true;
+        let err = MCIHostTransferError::from(err).blocks_set(lba, self.chunk_blocks);
+        self.card.stream_abort(true, err)
+    }
+}
+
+impl Drop for SdWriteStream<'_> {
+    fn drop(&mut self) {
+        if self.started && !self.stopped {
+            warn!("write stream dropped before finish, aborting");
+            if let Err(err) = self.card.stream_close(true) {
+                warn!("stop dropped write stream failed: {}", err);
+            }
+        }
+    }
+}
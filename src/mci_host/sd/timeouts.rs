@@ -0,0 +1,107 @@
+//! # SD Card Operation Timeouts
+//!
+//! Read, write and erase timeouts follow section 4.6.2 of the SD physical
+//! layer specification: standard capacity cards derive them from the CSD
+//! access time (TAAC/NSAC) and R2W factor, high capacity cards use fixed
+//! values. The card finishes programming a block within the write timeout,
+//! so that bounds the busy wait too.
+
+use core::time::Duration;
+
+use super::csd::SdCsd;
+
+/* upper limits and fixed values from the SD specification */
+const SD_READ_TIMEOUT_MAX: Duration = Duration::from_millis(100);
+const SD_WRITE_TIMEOUT_MAX: Duration = Duration::from_millis(250);
+const SDXC_WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+const SD_CMD_TIMEOUT: Duration = Duration::from_millis(100);
+const SD_BUSY_TIMEOUT: Duration = Duration::from_millis(600);
+/* the card may be inserted any time later, wait for it unless told otherwise */
+const SD_CARD_DETECT_TIMEOUT: Duration = Duration::MAX;
+
+/* TAAC time unit in ns and time value multiplied by 10 */
+const TAAC_UNIT_NS: [u64; 8] = [1, 10, 100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000];
+const TAAC_VALUE: [u64; 16] = [
+    0, 10, 12, 13, 15, 20, 25, 30, 35, 40, 45, 50, 55, 60, 70, 80,
+];
+
+/// Timeouts of the SD card operations.
+///
+/// Defaults are derived from the card registers during init, see
+/// [`SdCard::timeouts_set`](super::SdCard::timeouts_set) to override them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdTimeouts {
+    /// Command without data
+    pub cmd: Duration,
+    /// Read of one block
+    pub read: Duration,
+    /// Write of one block
+    pub write: Duration,
+    /// Card leaving the busy/programming state, the same as `write` once
+    /// derived from the CSD
+    pub busy: Duration,
+    /// Card insertion or removal, unbounded by default
+    pub card_detect: Duration,
+    /// Erase of one write block, scaled by the block count in
+    /// [`SdCard::erase_blocks`](super::SdCard::erase_blocks)
+    pub erase: Duration,
+}
+
+impl Default for SdTimeouts {
+    fn default() -> Self {
+        Self {
+            cmd: SD_CMD_TIMEOUT,
+            read: SD_READ_TIMEOUT_MAX,
+            write: SD_WRITE_TIMEOUT_MAX,
+            busy: SD_BUSY_TIMEOUT,
+            card_detect: SD_CARD_DETECT_TIMEOUT,
+            erase: SD_WRITE_TIMEOUT_MAX,
+        }
+    }
+}
+
+impl SdTimeouts {
+    /// Derive the timeouts from the card's CSD.
+    ///
+    /// # Arguments
+    ///
+    /// * `csd` - Decoded CSD register
+    /// * `sdxc` - Whether the card is an SDXC card
+    /// * `bus_clk_hz` - Card clock, used to convert NSAC clock cycles to time
+    pub(crate) fn from_csd(csd: &SdCsd, sdxc: bool, bus_clk_hz: u32) -> Self {
+        let mut timeouts = Self::default();
+
+        if csd.csd_structure != 0 {
+            /* CSD version 2.0 fixes TAAC/NSAC, timeouts are constants */
+            if sdxc {
+                timeouts.write = SDXC_WRITE_TIMEOUT;
+                timeouts.erase = SDXC_WRITE_TIMEOUT;
+            }
+            timeouts.busy = timeouts.write;
+            return timeouts;
+        }
+
+        let taac = csd.data_read_access_time1;
+        let taac_ns =
+            TAAC_UNIT_NS[(taac & 0x7) as usize] * TAAC_VALUE[((taac >> 3) & 0xF) as usize] / 10;
+        let nsac_ns = if bus_clk_hz == 0 {
+            0
+        } else {
+            csd.data_read_access_time2 as u64 * 100 * 1_000_000_000 / bus_clk_hz as u64
+        };
+
+        /* the timeout is 100 times the typical access time */
+        let read = Duration::from_nanos((taac_ns + nsac_ns) * 100);
+        if read.is_zero() {
+            /* reserved TAAC encoding, keep the maximum */
+            return timeouts;
+        }
+        let write = read * (1 << (csd.write_speed_factor & 0x7));
+        timeouts.read = read.min(SD_READ_TIMEOUT_MAX);
+        timeouts.write = write.min(SD_WRITE_TIMEOUT_MAX);
+        timeouts.erase = timeouts.write;
+        timeouts.busy = timeouts.write;
+        timeouts
+    }
+}
@@ -0,0 +1,144 @@
+//! # Write Verification
+//!
+//! Opt-in checks that written data reached the media. The card is asked how
+//! many blocks it wrote (ACMD22) and, at the stricter level, every write is
+//! read back and compared. A failed check names the first bad LBA. Data
+//! written earlier can be checked against a CRC-32 kept by the caller.
+
+use alloc::vec::Vec;
+
+use log::error;
+
+use crate::mci_host::err::{MCIHostError, MCIHostTransferError, MCIHostTransferStatus};
+use crate::tools::crc32_update;
+
+use super::SdCard;
+
+/// How [`SdCard`] makes sure written blocks reached the card.
+///
+/// Reading back only covers writes from a buffer, i.e.
+/// [`write_blocks`](SdCard::write_blocks) and [`write`](SdCard::write); the
+/// block count is checked for every closed-ended write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SdWriteVerify {
+    /// Trust the card once it took the data
+    #[default]
+    Off,
+    /// Compare the number of blocks the card reports written with the request
+    BlockCount,
+    /// Check the block count, then read the blocks back and compare them
+    ReadBack,
+}
+
+impl SdCard {
+    /// Get the write verification in effect.
+    pub fn write_verify(&self) -> SdWriteVerify {
+        self.write_verify
+    }
+
+    /// Set how writes are verified, [`SdWriteVerify::Off`] by default.
+    ///
+    /// Reading back doubles the time a write takes.
+    pub fn write_verify_set(&mut self, verify: SdWriteVerify) {
+        self.write_verify = verify;
+    }
+
+    /// Check the card wrote all `block_count` blocks from `start_block`.
+    pub(crate) fn written_blocks_verify(
+        &self,
+        start_block: u32,
+        block_count: u32,
+        written_blocks: u32,
+    ) -> MCIHostTransferStatus {
+        if self.write_verify == SdWriteVerify::Off || written_blocks >= block_count {
+            return Ok(());
+        }
+        error!(
+            "card wrote {} of {} block(s) from LBA {}",
+            written_blocks, block_count, start_block
+        );
+        Err(
+            MCIHostTransferError::from(MCIHostError::WrittenBlockCountMismatch)
+                .blocks_set(start_block + written_blocks, block_count - written_blocks),
+        )
+    }
+
+    /// Read back the blocks just written from `buffer` and compare them.
+    pub(crate) fn written_data_verify(
+        &mut self,
+        buffer: &[u32],
+        start_block: u32,
+        block_size: u32,
+        block_count: u32,
+    ) -> MCIHostTransferStatus {
+        if self.write_verify != SdWriteVerify::ReadBack {
+            return Ok(());
+        }
+
+        let mut read_back = Vec::new();
+        self.read(&mut read_back, start_block, block_size, block_count)?;
+
+        let block_words = (block_size / 4) as usize;
+        let blocks = buffer
+            .chunks(block_words)
+            .zip(read_back.chunks(block_words));
+        for (i, (written, read)) in blocks.enumerate() {
+            let lba = start_block + i as u32;
+            if let Some(word) = written.iter().zip(read).position(|(w, r)| w != r) {
+                error!(
+                    "LBA {} reads back 0x{:08x} at word {}, 0x{:08x} written",
+                    lba, read[word], word, written[word]
+                );
+                return Err(
+                    MCIHostTransferError::from(MCIHostError::WriteVerifyFailed).blocks_set(lba, 1)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Read `block_count` blocks from `start_block` and check their CRC-32
+    /// against `crc`, computed by the caller with [`crc32`](super::crc32)
+    /// when the data was written.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MCIHostError::WriteVerifyFailed`] for the whole range if
+    /// the CRC differs, or the error of the failed read.
+    pub fn crc32_verify(
+        &mut self,
+        start_block: u32,
+        block_count: u32,
+        crc: u32,
+    ) -> MCIHostTransferStatus {
+        let chunk_blocks = self
+            .base
+            .host
+            .as_ref()
+            .ok_or(MCIHostError::HostNotReady)?
+            .max_block_count
+            .get()
+            .max(1);
+
+        let mut read_crc = 0;
+        let mut buffer = Vec::new();
+        let mut lba = start_block;
+        let end = start_block.saturating_add(block_count);
+        while lba < end {
+            let blocks = (end - lba).min(chunk_blocks);
+            self.read_blocks(&mut buffer, lba, blocks)?;
+            read_crc = crc32_update(read_crc, buffer.iter().flat_map(|word| word.to_le_bytes()));
+            lba += blocks;
+        }
+
+        if read_crc != crc {
+            error!(
+                "LBA {}..{} read back with CRC-32 0x{:08x}, expected 0x{:08x}",
+                start_block, end, read_crc, crc
+            );
+            return Err(MCIHostTransferError::from(MCIHostError::WriteVerifyFailed)
+                .blocks_set(start_block, block_count));
+        }
+        Ok(())
+    }
+}
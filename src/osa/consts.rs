@@ -1,2 +1,4 @@
+#[cfg(feature = "osa-pool")]
 pub const SZ_1M: usize = 1024 * 1024;
+#[cfg(feature = "osa-pool")]
 pub const MAX_POOL_SIZE: usize = SZ_1M;
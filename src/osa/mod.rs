@@ -5,8 +5,11 @@
 //!
 //! ## Components
 //!
-//! - **FMemp**: Memory pool manager using TLSF
+//! - **FMemp**: Memory pool manager using TLSF, or forwarding to the global
+//!   allocator / `dma_api` depending on [`FMempStrategy`]
 //! - **PoolBuffer**: Safe wrapper for aligned memory allocations
+//! - **FMempStats**: Pool usage and fragmentation statistics
+//! - **OsaMutex / OsaEvent**: Mutex and event on top of the [`Kernel`](crate::Kernel) hooks
 //!
 //! ## Usage
 //!
@@ -18,23 +21,50 @@
 //!
 //! // Allocate aligned memory
 //! let buffer = osa_alloc_aligned(4096, 512).unwrap();
+//!
+//! // Inspect pool usage
+//! let stats = osa_stats();
 //! ```
+//!
+//! ## Features
+//!
+//! - `osa-pool` (default): Reserve the static Tlsf pool. Without it allocations
+//!   go to the global allocator unless another strategy is selected
+//! - `osa-debug`: Track every live allocation so leaks can be dumped with
+//!   [`osa_dump_live_allocations`]
 
 #![deny(missing_docs)]
-use core::{alloc::Layout, mem::MaybeUninit, ptr::NonNull};
+#[cfg(feature = "osa-pool")]
+use core::mem::MaybeUninit;
+use core::{alloc::Layout, ptr::NonNull};
 
+#[cfg(feature = "osa-debug")]
+use alloc::{collections::BTreeMap, vec::Vec};
+#[cfg(feature = "osa-pool")]
 use consts::MAX_POOL_SIZE;
+#[cfg(feature = "dma")]
+use dma_api::{DVec, Direction};
 use err::FMempError;
 use lazy_static::*;
-use pool_buffer::PoolBuffer;
+#[cfg(feature = "osa-debug")]
+use log::warn;
+use pool_buffer::{PoolBacking, PoolBuffer};
 use rlsf::Tlsf;
 use spin::Mutex;
+#[cfg(feature = "osa-debug")]
+pub use stats::FMempAllocRecord;
+use stats::FMempCounters;
+pub use stats::FMempStats;
+pub use sync::{OsaEvent, OsaMutex, OsaMutexGuard};
 
 mod consts;
 mod err;
 pub mod pool_buffer;
+mod stats;
+mod sync;
 
 /// Memory managed by Tlsf pool
+#[cfg(feature = "osa-pool")]
 static mut POOL: [MaybeUninit<u8>; MAX_POOL_SIZE] = [MaybeUninit::uninit(); MAX_POOL_SIZE];
 
 /// Tlsf memory pool controller.
@@ -45,6 +75,43 @@ pub struct FMemp<'a> {
     tlsf_ptr: Tlsf<'a, u32, u32, 32, 32>,
     /// Whether the pool is initialized
     is_ready: bool,
+    /// Start address of the pool as seen by Tlsf
+    pool_start: usize,
+    /// Length of the pool as seen by Tlsf
+    pool_len: usize,
+    /// Backend used for new allocations
+    strategy: FMempStrategy,
+    /// Usage counters
+    counters: FMempCounters,
+    /// Live allocations keyed by address
+    #[cfg(feature = "osa-debug")]
+    live: BTreeMap<usize, FMempAllocRecord>,
+}
+
+/// Backend used by [`osa_alloc`] and [`osa_alloc_aligned`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FMempStrategy {
+    /// Static Tlsf pool
+    #[cfg(feature = "osa-pool")]
+    Pool,
+    /// The global allocator of the application
+    Global,
+    /// Cached memory mapped through `dma_api`, the buffer also carries its
+    /// bus address. The [`PoolBuffer`] accessors write back and invalidate
+    /// the cache around every CPU access and in
+    /// [`bus_addr`](PoolBuffer::bus_addr); memory reached through
+    /// [`addr`](PoolBuffer::addr) is up to the user
+    #[cfg(feature = "dma")]
+    Dma,
+}
+
+impl Default for FMempStrategy {
+    fn default() -> Self {
+        #[cfg(feature = "osa-pool")]
+        return FMempStrategy::Pool;
+        #[cfg(not(feature = "osa-pool"))]
+        return FMempStrategy::Global;
+    }
 }
 
 lazy_static! {
@@ -65,13 +132,30 @@ impl<'a> FMemp<'a> {
         Self {
             tlsf_ptr: Tlsf::new(),
             is_ready: false,
+            pool_start: 0,
+            pool_len: 0,
+            strategy: FMempStrategy::default(),
+            counters: FMempCounters::new(),
+            #[cfg(feature = "osa-debug")]
+            live: BTreeMap::new(),
         }
     }
 
     unsafe fn init(&mut self) {
-        unsafe {
-            self.tlsf_ptr.insert_free_block(&mut POOL[..]);
-            self.is_ready = true;
+        /* the pool memory can only be handed to Tlsf once */
+        #[cfg(feature = "osa-pool")]
+        if !self.is_ready {
+            unsafe {
+                let pool = NonNull::slice_from_raw_parts(
+                    NonNull::new_unchecked(&raw mut POOL as *mut u8),
+                    MAX_POOL_SIZE,
+                );
+                if let Some(len) = self.tlsf_ptr.insert_free_block_ptr(pool) {
+                    self.pool_start = pool.as_ptr() as *mut u8 as usize;
+                    self.pool_len = len.get();
+                    self.is_ready = true;
+                }
+            }
         }
     }
 
@@ -80,31 +164,129 @@ impl<'a> FMemp<'a> {
         size: usize,
         align: usize,
     ) -> Result<PoolBuffer, FMempError> {
-        unsafe {
-            let layout = Layout::from_size_align_unchecked(size, align);
-            if let Some(result) = self.tlsf_ptr.allocate(layout) {
-                let buffer = PoolBuffer::new(size, result);
-                Ok(buffer)
-            } else {
-                Err(FMempError::BadMalloc)
-            }
+        /* a zero-sized layout is undefined behaviour for the global allocator */
+        if size == 0 {
+            return Err(FMempError::InvalidBuf);
         }
+        let layout = Layout::from_size_align(size, align).map_err(|_| FMempError::InvalidBuf)?;
+        let buffer = match self.strategy {
+            #[cfg(feature = "osa-pool")]
+            FMempStrategy::Pool => self
+                .tlsf_ptr
+                .allocate(layout)
+                .map(|addr| PoolBuffer::with_backing(size, addr, PoolBacking::Pool { align })),
+            FMempStrategy::Global => NonNull::new(unsafe { alloc::alloc::alloc(layout) })
+                .map(|addr| PoolBuffer::with_backing(size, addr, PoolBacking::Global { align })),
+            #[cfg(feature = "dma")]
+            FMempStrategy::Dma => DVec::zeros(size, align, Direction::Bidirectional).map(|dvec| {
+                let addr = NonNull::new(dvec.as_ptr() as *mut u8).unwrap();
+                PoolBuffer::with_backing(size, addr, PoolBacking::Dma(dvec))
+            }),
+        };
+
+        let Some(buffer) = buffer else {
+            self.counters.on_alloc_failed();
+            return Err(FMempError::BadMalloc);
+        };
+        #[cfg(feature = "osa-debug")]
+        self.live.insert(
+            buffer.addr().as_ptr() as usize,
+            FMempAllocRecord {
+                addr: buffer.addr().as_ptr() as usize,
+                size,
+                align,
+                seq: self.counters.total_allocs,
+            },
+        );
+        self.counters.on_alloc(size);
+        Ok(buffer)
     }
 
-    unsafe fn dealloc(&mut self, addr: NonNull<u8>, size: usize) {
-        unsafe {
-            self.tlsf_ptr.deallocate(addr, size);
+    unsafe fn dealloc(&mut self, addr: NonNull<u8>, size: usize, backing: &PoolBacking) {
+        match backing {
+            #[cfg(feature = "osa-pool")]
+            PoolBacking::Pool { align } => unsafe {
+                self.tlsf_ptr.deallocate(addr, *align);
+            },
+            PoolBacking::Global { align } => unsafe {
+                alloc::alloc::dealloc(
+                    addr.as_ptr(),
+                    Layout::from_size_align_unchecked(size, *align),
+                );
+            },
+            /* DVec unmaps and frees itself when the buffer is dropped */
+            #[cfg(feature = "dma")]
+            PoolBacking::Dma(_) => {}
+        }
+        #[cfg(feature = "osa-debug")]
+        self.live.remove(&(addr.as_ptr() as usize));
+        self.counters.on_dealloc(size);
+    }
+
+    /// Collect usage counters and walk the free list.
+    ///
+    /// Walking the pool is linear in the number of blocks, so this is meant
+    /// for diagnostics rather than hot paths.
+    pub fn stats(&self) -> FMempStats {
+        let mut stats = FMempStats {
+            pool_size: self.pool_len,
+            bytes_in_use: self.counters.bytes_in_use,
+            peak_bytes_in_use: self.counters.peak_bytes_in_use,
+            alloc_count: self.counters.alloc_count,
+            total_allocs: self.counters.total_allocs,
+            failed_allocs: self.counters.failed_allocs,
+            ..Default::default()
+        };
+        if !self.is_ready {
+            return stats;
+        }
+
+        let pool = NonNull::slice_from_raw_parts(
+            unsafe { NonNull::new_unchecked(self.pool_start as *mut u8) },
+            self.pool_len,
+        );
+        // Safety: `pool` is exactly the range `init` inserted into Tlsf
+        for block in unsafe { self.tlsf_ptr.iter_blocks(pool) } {
+            if block.is_occupied() {
+                continue;
+            }
+            let payload = block.max_payload_size();
+            stats.free_bytes += payload;
+            stats.free_blocks += 1;
+            stats.largest_free_block = stats.largest_free_block.max(payload);
         }
+        stats
+    }
+
+    /// Live allocations ordered by address
+    #[cfg(feature = "osa-debug")]
+    pub fn live_allocations(&self) -> Vec<FMempAllocRecord> {
+        self.live.values().copied().collect()
     }
 }
 
 /// Init memory pool with size of ['MAX_POOL_SIZE']
+///
+/// Does nothing when the `osa-pool` feature is disabled.
 pub fn osa_init() {
     unsafe {
         GLOBAL_FMEMP.lock().init();
     }
 }
 
+/// Select where subsequent allocations come from.
+///
+/// Buffers that are already allocated keep their backing and are returned
+/// to it when dropped, so switching at runtime is safe.
+pub fn osa_set_strategy(strategy: FMempStrategy) {
+    GLOBAL_FMEMP.lock().strategy = strategy;
+}
+
+/// Get the current allocation strategy
+pub fn osa_strategy() -> FMempStrategy {
+    GLOBAL_FMEMP.lock().strategy
+}
+
 /// Alloc 'size' bytes space, aligned to 64 KiB by default
 pub fn osa_alloc(size: usize) -> Result<PoolBuffer, FMempError> {
     unsafe { GLOBAL_FMEMP.lock().alloc_aligned(size, size_of::<usize>()) }
@@ -116,8 +298,46 @@ pub fn osa_alloc_aligned(size: usize, align: usize) -> Result<PoolBuffer, FMempE
 }
 
 /// Dealloc 'size' bytes space from 'addr'
+///
+/// `addr` must come from the Tlsf pool with an alignment of at most
+/// `size`. Dropping a [`PoolBuffer`] releases it through the right backend
+/// and is preferred over calling this directly.
+///
+/// Only available with the `osa-pool` feature, without it there is no
+/// pool to return memory to.
+#[cfg(feature = "osa-pool")]
 pub fn osa_dealloc(addr: NonNull<u8>, size: usize) {
     unsafe {
-        GLOBAL_FMEMP.lock().dealloc(addr, size);
+        GLOBAL_FMEMP
+            .lock()
+            .dealloc(addr, size, &PoolBacking::Pool { align: size });
+    }
+}
+
+/// Get a snapshot of the memory pool usage
+pub fn osa_stats() -> FMempStats {
+    GLOBAL_FMEMP.lock().stats()
+}
+
+/// Get all allocations that have not been freed yet
+#[cfg(feature = "osa-debug")]
+pub fn osa_live_allocations() -> Vec<FMempAllocRecord> {
+    GLOBAL_FMEMP.lock().live_allocations()
+}
+
+/// Dump all allocations that have not been freed yet to the log
+#[cfg(feature = "osa-debug")]
+pub fn osa_dump_live_allocations() {
+    let fmemp = GLOBAL_FMEMP.lock();
+    let stats = fmemp.stats();
+    warn!(
+        "osa: {} live allocations, {} bytes in use, peak {} bytes",
+        stats.alloc_count, stats.bytes_in_use, stats.peak_bytes_in_use
+    );
+    for record in fmemp.live.values() {
+        warn!(
+            "  #{} addr 0x{:x} size {} align {}",
+            record.seq, record.addr, record.size, record.align
+        );
     }
 }
@@ -7,19 +7,60 @@ use core::{
 };
 
 use alloc::vec::Vec;
-
-use super::{err::FMempError, osa_dealloc};
+#[cfg(feature = "dma")]
+use dma_api::DVec;
+
+#[cfg(feature = "dma")]
+use crate::mci::constants::flush;
+
+use super::{GLOBAL_FMEMP, err::FMempError};
+
+/// Backend a [`PoolBuffer`] was allocated from
+pub(crate) enum PoolBacking {
+    /// Tlsf pool, alignment used for allocation
+    #[cfg(feature = "osa-pool")]
+    Pool { align: usize },
+    /// Global allocator, alignment used for allocation
+    Global { align: usize },
+    /// Cached `dma_api` allocation, mapped for the device and kept coherent
+    /// by [`PoolBuffer::dma_sync`]
+    #[cfg(feature = "dma")]
+    Dma(DVec<u8>),
+}
 
 /// PoolBuffer definition
 pub struct PoolBuffer {
     size: usize,
     addr: NonNull<u8>,
+    backing: PoolBacking,
 }
 
 impl PoolBuffer {
     /// Construct a PoolBuffer
+    ///
+    /// `addr` must come from the Tlsf pool, like for [`osa_dealloc`](super::osa_dealloc).
+    #[cfg(feature = "osa-pool")]
     pub fn new(size: usize, addr: NonNull<u8>) -> Self {
-        Self { size, addr }
+        Self::with_backing(size, addr, PoolBacking::Pool { align: size })
+    }
+
+    /// Construct a PoolBuffer released to `backing` when dropped
+    pub(crate) fn with_backing(size: usize, addr: NonNull<u8>, backing: PoolBacking) -> Self {
+        Self {
+            size,
+            addr,
+            backing,
+        }
+    }
+
+    /// Write back and drop the cache lines of a `dma_api` buffer, so the
+    /// CPU and the device see the same memory. Nothing to do for the others.
+    fn dma_sync(&self) {
+        #[cfg(feature = "dma")]
+        if let PoolBacking::Dma(_) = self.backing {
+            // Safety: the range is exactly the allocation
+            unsafe { flush(self.addr.as_ptr(), self.size) };
+        }
     }
 
     /// Construct from `&[T]`
@@ -33,6 +74,7 @@ impl PoolBuffer {
             // equivalent to memcpy in C
             copy_nonoverlapping(src.as_ptr() as *mut u8, self.addr.as_ptr(), len);
         }
+        self.dma_sync();
 
         Ok(())
     }
@@ -43,6 +85,7 @@ impl PoolBuffer {
         if !self.size().is_multiple_of(size) {
             return Err(FMempError::SizeNotAligned);
         }
+        self.dma_sync();
 
         unsafe {
             let result = from_raw_parts(self.addr.as_ptr() as *const T, self.size() / size);
@@ -56,6 +99,24 @@ impl PoolBuffer {
         if !self.size().is_multiple_of(size) {
             return Err(FMempError::SizeNotAligned);
         }
+        self.dma_sync();
+
+        unsafe {
+            let result = from_raw_parts_mut(self.addr.as_ptr() as *mut T, self.size() / size);
+            Ok(result)
+        }
+    }
+
+    /// Borrow the buffer mutably as a `&mut [T]`
+    ///
+    /// Writes through the slice reach a DMA buffer's device once
+    /// [`bus_addr`](Self::bus_addr) is taken after them.
+    pub fn as_mut_slice<T>(&mut self) -> Result<&mut [T], FMempError> {
+        let size = size_of::<T>();
+        if !self.size().is_multiple_of(size) {
+            return Err(FMempError::SizeNotAligned);
+        }
+        self.dma_sync();
 
         unsafe {
             let result = from_raw_parts_mut(self.addr.as_ptr() as *mut T, self.size() / size);
@@ -74,6 +135,7 @@ impl PoolBuffer {
         unsafe {
             write_bytes(self.addr.as_ptr(), 0, self.size);
         }
+        self.dma_sync();
     }
 
     /// Get size
@@ -85,16 +147,36 @@ impl PoolBuffer {
     pub fn addr(&self) -> NonNull<u8> {
         self.addr
     }
+
+    /// Get bus address, only known for `FMempStrategy::Dma` allocations
+    ///
+    /// Hands the buffer to the device: whatever the CPU wrote is written
+    /// back from the cache first. Take it right before starting the DMA.
+    #[cfg(feature = "dma")]
+    pub fn bus_addr(&self) -> Option<u64> {
+        match &self.backing {
+            PoolBacking::Dma(dvec) => {
+                self.dma_sync();
+                Some(dvec.bus_addr())
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Drop for PoolBuffer {
     fn drop(&mut self) {
-        osa_dealloc(self.addr, self.size);
+        unsafe {
+            GLOBAL_FMEMP
+                .lock()
+                .dealloc(self.addr, self.size, &self.backing);
+        }
     }
 }
 
 impl From<PoolBuffer> for Vec<u32> {
     fn from(val: PoolBuffer) -> Self {
+        val.dma_sync();
         unsafe {
             let slice = from_raw_parts(val.addr.as_ptr() as *const u32, val.size / 4);
             slice.to_vec()
@@ -0,0 +1,101 @@
+//! # Memory Pool Statistics
+//!
+//! Usage counters and free-list inspection for the TLSF pool.
+//!
+//! With the `osa-debug` feature enabled every live [`PoolBuffer`](super::pool_buffer::PoolBuffer)
+//! is also recorded with its size and alignment, so leaked buffers can be
+//! listed on demand.
+
+/// Snapshot of the memory pool usage.
+///
+/// Returned by [`osa_stats`](super::osa_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FMempStats {
+    /// Total size of the managed pool in bytes
+    pub pool_size: usize,
+    /// Bytes currently handed out to callers
+    pub bytes_in_use: usize,
+    /// Highest value `bytes_in_use` has reached since init
+    pub peak_bytes_in_use: usize,
+    /// Number of allocations currently alive
+    pub alloc_count: usize,
+    /// Number of successful allocations since init
+    pub total_allocs: usize,
+    /// Number of allocation requests that failed since init
+    pub failed_allocs: usize,
+    /// Sum of the payload sizes of all free blocks
+    pub free_bytes: usize,
+    /// Number of free blocks
+    pub free_blocks: usize,
+    /// Payload size of the largest free block
+    pub largest_free_block: usize,
+}
+
+impl FMempStats {
+    /// Free-space fragmentation in percent.
+    ///
+    /// `0` means all free memory is one contiguous block, values close to
+    /// `100` mean the free memory is split into many small blocks and large
+    /// allocations are likely to fail even though `free_bytes` is high.
+    pub fn fragmentation(&self) -> u32 {
+        if self.free_bytes == 0 {
+            return 0;
+        }
+        let contiguous = self.largest_free_block as u64 * 100 / self.free_bytes as u64;
+        100 - contiguous as u32
+    }
+}
+
+/// Usage counters maintained by [`FMemp`](super::FMemp) on every alloc/dealloc.
+#[derive(Debug)]
+pub(crate) struct FMempCounters {
+    pub(crate) bytes_in_use: usize,
+    pub(crate) peak_bytes_in_use: usize,
+    pub(crate) alloc_count: usize,
+    pub(crate) total_allocs: usize,
+    pub(crate) failed_allocs: usize,
+}
+
+impl FMempCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            bytes_in_use: 0,
+            peak_bytes_in_use: 0,
+            alloc_count: 0,
+            total_allocs: 0,
+            failed_allocs: 0,
+        }
+    }
+
+    pub(crate) fn on_alloc(&mut self, size: usize) {
+        self.bytes_in_use += size;
+        self.peak_bytes_in_use = self.peak_bytes_in_use.max(self.bytes_in_use);
+        self.alloc_count += 1;
+        self.total_allocs += 1;
+    }
+
+    pub(crate) fn on_alloc_failed(&mut self) {
+        self.failed_allocs += 1;
+    }
+
+    pub(crate) fn on_dealloc(&mut self, size: usize) {
+        self.bytes_in_use = self.bytes_in_use.saturating_sub(size);
+        self.alloc_count = self.alloc_count.saturating_sub(1);
+    }
+}
+
+/// A live allocation recorded in debug mode.
+///
+/// Returned by [`osa_live_allocations`](super::osa_live_allocations).
+#[cfg(feature = "osa-debug")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FMempAllocRecord {
+    /// Start address of the buffer
+    pub addr: usize,
+    /// Requested size in bytes
+    pub size: usize,
+    /// Requested alignment in bytes
+    pub align: usize,
+    /// Sequence number of the allocation, starting from 0 after init
+    pub seq: usize,
+}
@@ -0,0 +1,108 @@
+//! # Synchronization Primitives
+//!
+//! Mutex and event built on the [`Kernel`](crate::Kernel) mutex and event
+//! hooks, so they block the calling task on an RTOS and spin on bare metal.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use crate::{event_notify, event_wait, mutex_lock, mutex_unlock};
+
+/// Mutual exclusion lock backed by [`Kernel::mutex_lock`](crate::Kernel::mutex_lock).
+pub struct OsaMutex<T> {
+    lock: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: access to `data` is serialized by the kernel mutex.
+unsafe impl<T: Send> Send for OsaMutex<T> {}
+unsafe impl<T: Send> Sync for OsaMutex<T> {}
+
+impl<T> OsaMutex<T> {
+    /// Create an unlocked mutex.
+    pub const fn new(data: T) -> Self {
+        Self {
+            lock: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Acquire the mutex, blocking until it is available.
+    pub fn lock(&self) -> OsaMutexGuard<'_, T> {
+        mutex_lock(&self.lock);
+        OsaMutexGuard { mutex: self }
+    }
+
+    /// Consume the mutex and return the protected value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+/// Scoped access to the value of an [`OsaMutex`], unlocking on drop.
+pub struct OsaMutexGuard<'a, T> {
+    mutex: &'a OsaMutex<T>,
+}
+
+impl<T> Deref for OsaMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for OsaMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for OsaMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        mutex_unlock(&self.mutex.lock);
+    }
+}
+
+/// Auto-reset event backed by [`Kernel::event_wait`](crate::Kernel::event_wait).
+///
+/// A notification wakes one waiter and is consumed by it.
+pub struct OsaEvent {
+    flag: AtomicBool,
+}
+
+impl OsaEvent {
+    /// Create an event in the non-notified state.
+    pub const fn new() -> Self {
+        Self {
+            flag: AtomicBool::new(false),
+        }
+    }
+
+    /// Block until the event is notified or `timeout` passes.
+    ///
+    /// Returns `false` on timeout.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        event_wait(&self.flag, timeout)
+    }
+
+    /// Notify the event. Safe to call from interrupt context.
+    pub fn notify(&self) {
+        event_notify(&self.flag)
+    }
+
+    /// Drop a pending notification.
+    pub fn clear(&self) {
+        self.flag.store(false, Ordering::Release);
+    }
+}
+
+impl Default for OsaEvent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,6 +1,7 @@
 #![allow(unused)]
 
-use crate::sleep;
+use crate::tools::Deadline;
+use crate::{sleep, yield_now};
 use bitflags::{Flags, bitflags};
 use core::{marker::PhantomData, ops, ptr::NonNull, time::Duration};
 use log::info;
@@ -115,33 +116,97 @@ impl<E: RegError> Reg<E> {
         self.addr
     }
 
-    pub fn wait_for<R: FlagReg, F: Fn(R) -> bool>(
+    /// Poll until `f` accepts the register value or `timeout` has passed.
+    ///
+    /// Polls back off adaptively, see [`Backoff`].
+    pub fn poll_until<R: FlagReg, F: Fn(R) -> bool>(
         &self,
         f: F,
-        interval: Duration,
-        try_count: Option<usize>,
+        timeout: Duration,
     ) -> Result<(), E> {
-        for _ in 0..try_count.unwrap_or(usize::MAX) {
+        let mut backoff = Backoff::new(timeout);
+        loop {
             if f(self.read_reg::<R>()) {
                 return Ok(());
             }
+            if !backoff.snooze() {
+                return Err(E::timeout());
+            }
+        }
+    }
+}
+
+/* polls spent busy-waiting, then yielding, before the first sleep */
+const BACKOFF_SPIN_COUNT: u32 = 128;
+const BACKOFF_YIELD_COUNT: u32 = 64;
+/* first and longest sleep between polls */
+const BACKOFF_MIN_STEP: Duration = Duration::from_micros(1);
+const BACKOFF_MAX_STEP: Duration = Duration::from_micros(100);
+
+/// Adaptive wait between polls of a completion condition.
+///
+/// Completions that arrive within a few microseconds are caught by busy-waiting,
+/// then the wait lets other tasks run through [`Kernel::yield_now`](crate::Kernel::yield_now)
+/// and finally sleeps through [`Kernel::sleep`](crate::Kernel::sleep) in doubling
+/// steps, so long transfers don't keep the CPU spinning. The timeout is a
+/// deadline on [`Kernel::now`](crate::Kernel::now).
+///
+/// Poll the condition, then call [`snooze`](Self::snooze) until it returns
+/// `false`, which means the timeout expired:
+///
+/// ```ignore
+/// let mut backoff = Backoff::new(timeout);
+/// while !done() {
+///     if !backoff.snooze() {
+///         return Err(MCIError::Timeout);
+///     }
+/// }
+/// ```
+pub struct Backoff {
+    spins: u32,
+    step: Duration,
+    deadline: Deadline,
+}
 
-            sleep(interval);
+impl Backoff {
+    /// Start waiting, the deadline is `timeout` from now.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            spins: 0,
+            step: BACKOFF_MIN_STEP,
+            deadline: Deadline::after(timeout),
         }
-        Err(E::timeout())
     }
 
-    pub fn retry_for<R: FlagReg, F: Fn(R) -> bool>(
-        &self,
-        f: F,
-        try_count: Option<usize>,
-    ) -> Result<(), E> {
-        for _ in 0..try_count.unwrap_or(usize::MAX) {
-            if f(self.read_reg::<R>()) {
-                return Ok(());
-            }
+    /// Wait before the next poll, returns `false` once the deadline has passed.
+    pub fn snooze(&mut self) -> bool {
+        if self.deadline.expired() {
+            return false;
+        }
+        if self.spins < BACKOFF_SPIN_COUNT {
+            self.spins += 1;
+            core::hint::spin_loop();
+            return true;
+        }
+        if self.spins < BACKOFF_SPIN_COUNT + BACKOFF_YIELD_COUNT {
+            self.spins += 1;
+            yield_now();
+            return true;
         }
-        Err(E::timeout())
+        sleep(self.step);
+        self.step = (self.step * 2).min(BACKOFF_MAX_STEP);
+        true
+    }
+
+    /// Time left until the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.deadline.remaining()
+    }
+
+    /// Go back to busy-waiting after the condition made progress, the deadline stays.
+    pub fn progress(&mut self) {
+        self.spins = 0;
+        self.step = BACKOFF_MIN_STEP;
     }
 }
 
@@ -1,8 +1,31 @@
 use core::alloc::Layout;
+use core::time::Duration;
 
 use alloc::vec::Vec;
 use log::{debug, info};
 
+use crate::now;
+
+/// Point in time a wait gives up at, measured with [`Kernel::now`](crate::Kernel::now).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline(Duration);
+
+impl Deadline {
+    /// Deadline `timeout` from now.
+    pub(crate) fn after(timeout: Duration) -> Self {
+        Self(now().saturating_add(timeout))
+    }
+
+    pub(crate) fn expired(&self) -> bool {
+        now() >= self.0
+    }
+
+    /// Time left until the deadline, zero once it has passed.
+    pub(crate) fn remaining(&self) -> Duration {
+        self.0.saturating_sub(now())
+    }
+}
+
 pub fn swap_half_word_byte_sequence_u32(value: u32) -> u32 {
     // Swap each 16-bit halfword
     ((value & 0x0000FFFF) << 16) | ((value & 0xFFFF0000) >> 16)
@@ -15,6 +38,42 @@ pub fn swap_word_byte_sequence_u32(value: u32) -> u32 {
         | ((value & 0xFF000000) >> 24)
 }
 
+/* CRC-32 (IEEE 802.3, reflected) lookup table */
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Continue the CRC-32 `crc` of the data before `bytes`, 0 to start.
+pub(crate) fn crc32_update(crc: u32, bytes: impl IntoIterator<Item = u8>) -> u32 {
+    let crc = bytes.into_iter().fold(!crc, |crc, byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+/// CRC-32 of `bytes`, the IEEE 802.3 one computed by zlib.
+///
+/// Block data in `u32` words is taken as little-endian bytes.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes.iter().copied())
+}
+
 #[allow(dead_code)]
 pub fn realign_vec(input: Vec<u32>, align: usize) -> Result<Vec<u32>, &'static str> {
     let element_count = input.len();
@@ -8,14 +8,19 @@ extern crate alloc;
 mod tests {
     use core::time::Duration;
 
-    use alloc::vec::Vec;
+    use alloc::{vec, vec::Vec};
     use bare_test::{
         globals::{PlatformInfoKind, global_val},
         mem::iomap,
-        time::spin_delay,
+        time::{since_boot, spin_delay},
     };
     use log::*;
-    use phytium_mci::{iopad::PAD_ADDRESS, sd::SdCard, *};
+    use phytium_mci::{
+        err::{MCIHostError, MCIHostTransferError, MCIHostTransferStatus},
+        iopad::PAD_ADDRESS,
+        sd::{BlockDevice, CachedSdCard, SdCard, crc32},
+        *,
+    };
 
     const SD_START_BLOCK: u32 = 131072;
     const SD_USE_BLOCK: u32 = 1;
@@ -72,6 +77,202 @@ mod tests {
         info!("test_work passed\n");
     }
 
+    /* in-memory backing store for the block cache tests */
+    struct MemDisk {
+        data: Vec<u32>,
+        reads: u32,  // read transfers
+        writes: u32, // write transfers
+    }
+
+    impl MemDisk {
+        fn new(blocks: u32) -> Self {
+            let data = (0..blocks * SD_BLOCK_SIZE / 4).collect();
+            Self {
+                data,
+                reads: 0,
+                writes: 0,
+            }
+        }
+
+        fn block(&self, lba: u32) -> &[u32] {
+            let words = (SD_BLOCK_SIZE / 4) as usize;
+            &self.data[lba as usize * words..(lba as usize + 1) * words]
+        }
+    }
+
+    impl BlockDevice for MemDisk {
+        fn block_size(&self) -> u32 {
+            SD_BLOCK_SIZE
+        }
+
+        fn block_count(&self) -> u32 {
+            self.data.len() as u32 / (SD_BLOCK_SIZE / 4)
+        }
+
+        fn read_blocks(
+            &mut self,
+            buffer: &mut Vec<u32>,
+            start_block: u32,
+            block_count: u32,
+        ) -> MCIHostTransferStatus {
+            self.reads += 1;
+            let words = (SD_BLOCK_SIZE / 4) as usize;
+            let start = start_block as usize * words;
+            buffer.clear();
+            buffer.extend_from_slice(&self.data[start..start + block_count as usize * words]);
+            Ok(())
+        }
+
+        fn write_blocks(
+            &mut self,
+            buffer: &mut Vec<u32>,
+            start_block: u32,
+            block_count: u32,
+        ) -> MCIHostTransferStatus {
+            self.writes += 1;
+            let words = (SD_BLOCK_SIZE / 4) as usize;
+            let start = start_block as usize * words;
+            let len = block_count as usize * words;
+            self.data[start..start + len].copy_from_slice(&buffer[..len]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cache_hit() {
+        let mut store = vec![0u32; 4 * (SD_BLOCK_SIZE / 4) as usize];
+        let mut cache = CachedSdCard::with_buffer(MemDisk::new(64), &mut store).unwrap();
+        assert_eq!(cache.sectors(), 4);
+        cache.read_ahead_set(0);
+
+        let mut buf = Vec::new();
+        cache.read(&mut buf, 10, 2).unwrap();
+        assert_eq!(buf[..], cache.inner().data[10 * 128..12 * 128]);
+        cache.read(&mut buf, 10, 2).unwrap();
+        assert_eq!(buf[..], cache.inner().data[10 * 128..12 * 128]);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+        assert_eq!(cache.inner().reads, 1);
+
+        /* only the missing block goes to the device */
+        cache.read(&mut buf, 11, 2).unwrap();
+        assert_eq!(buf[..], cache.inner().data[11 * 128..13 * 128]);
+        assert_eq!(cache.inner().reads, 2);
+
+        assert!(cache.read(&mut buf, 63, 2).is_err());
+        info!("test_cache_hit passed\n");
+    }
+
+    #[test]
+    fn test_cache_write_back() {
+        let mut store = vec![0u32; 2 * (SD_BLOCK_SIZE / 4) as usize];
+        let mut cache = CachedSdCard::with_buffer(MemDisk::new(64), &mut store).unwrap();
+
+        let data: Vec<u32> = (0..4 * 128).map(|i| 0xA5A5_0000 | i).collect();
+        cache.write(&data[..2 * 128], 20, 2).unwrap();
+        assert_eq!(cache.dirty_blocks(), 2);
+        assert_eq!(cache.inner().writes, 0);
+
+        /* a dirty block is read back from the cache */
+        let mut buf = Vec::new();
+        cache.read(&mut buf, 20, 1).unwrap();
+        assert_eq!(buf[..], data[..128]);
+
+        /* the least recently used block is written back to make room */
+        cache.write(&data[2 * 128..3 * 128], 22, 1).unwrap();
+        assert_eq!(cache.inner().writes, 1);
+        assert_eq!(cache.inner().block(21), &data[128..2 * 128]);
+        assert_ne!(cache.inner().block(20), &data[..128]);
+
+        cache.write(&data[3 * 128..], 23, 1).unwrap();
+        assert_eq!(cache.stats().write_backs, 2);
+
+        /* consecutive dirty blocks are flushed in one transfer */
+        let writes = cache.inner().writes;
+        cache.flush().unwrap();
+        assert_eq!(cache.dirty_blocks(), 0);
+        assert_eq!(cache.inner().writes, writes + 1);
+        for lba in 20..24 {
+            let i = (lba - 20) as usize * 128;
+            assert_eq!(cache.inner().block(lba), &data[i..i + 128]);
+        }
+
+        assert!(cache.write(&data[..128], 20, 2).is_err());
+
+        /* taking the device back writes the dirty blocks first */
+        cache.write(&data[..128], 30, 1).unwrap();
+        let disk = cache.into_inner().map_err(|(_, err)| err).unwrap();
+        assert_eq!(disk.block(30), &data[..128]);
+
+        /* unless they are explicitly thrown away */
+        let mut cache = CachedSdCard::new(disk, 2).unwrap();
+        cache.write(&data[..128], 31, 1).unwrap();
+        let disk = cache.into_inner_discard();
+        assert_ne!(disk.block(31), &data[..128]);
+        info!("test_cache_write_back passed\n");
+    }
+
+    #[test]
+    fn test_cache_read_ahead() {
+        let mut cache = CachedSdCard::new(MemDisk::new(64), 16).unwrap();
+        cache.read_ahead_set(4);
+
+        let mut buf = Vec::new();
+        cache.read(&mut buf, 0, 2).unwrap();
+        assert_eq!(cache.stats().read_ahead, 0);
+
+        /* carrying on from the last read fetches 4 blocks more */
+        cache.read(&mut buf, 2, 2).unwrap();
+        assert_eq!(cache.inner().reads, 2);
+        assert_eq!(cache.stats().read_ahead, 4);
+
+        cache.read(&mut buf, 4, 4).unwrap();
+        assert_eq!(buf[..], cache.inner().data[4 * 128..8 * 128]);
+        assert_eq!(cache.inner().reads, 2);
+        assert_eq!(cache.stats().hits, 4);
+
+        /* a random read doesn't, nor past the end of the device */
+        cache.read(&mut buf, 40, 1).unwrap();
+        assert_eq!(cache.stats().read_ahead, 4);
+        cache.read(&mut buf, 62, 2).unwrap();
+        cache.read(&mut buf, 41, 1).unwrap();
+        assert_eq!(cache.stats().read_ahead, 4);
+        cache.read(&mut buf, 42, 1).unwrap();
+        cache.read(&mut buf, 61, 1).unwrap();
+        cache.read(&mut buf, 62, 2).unwrap();
+        assert_eq!(cache.stats().read_ahead, 8);
+        info!("test_cache_read_ahead passed\n");
+    }
+
+    #[test]
+    fn test_crc32() {
+        /* check value of the CRC-32 catalogue */
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+        let words = [0x3433_3231u32, 0x3837_3635];
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        assert_eq!(crc32(&bytes), crc32(b"12345678"));
+        info!("test_crc32 passed\n");
+    }
+
+    #[test]
+    fn test_transfer_error_lba() {
+        /* a read-back mismatch names the failing block, the chunk doesn't override it */
+        let err = MCIHostTransferError::from(MCIHostError::WriteVerifyFailed)
+            .blocks_set(SD_START_BLOCK + 5, 1)
+            .blocks_set_if_unset(SD_START_BLOCK, SD_MAX_RW_BLK);
+        assert_eq!(err.lba, Some(SD_START_BLOCK + 5));
+        assert_eq!(err.block_count, Some(1));
+
+        /* a failure with no blocks of its own reports the chunk */
+        let err = MCIHostTransferError::from(MCIHostError::DataCrcError)
+            .blocks_set_if_unset(SD_START_BLOCK, SD_MAX_RW_BLK);
+        assert_eq!(err.lba, Some(SD_START_BLOCK));
+        assert_eq!(err.block_count, Some(SD_MAX_RW_BLK));
+        info!("test_transfer_error_lba passed\n");
+    }
+
     fn sleep(duration: Duration) {
         spin_delay(duration);
     }
@@ -82,6 +283,10 @@ mod tests {
         fn sleep(duration: Duration) {
             sleep(duration);
         }
+
+        fn now() -> Duration {
+            since_boot()
+        }
     }
 
     set_impl!(KernelImpl);